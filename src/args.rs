@@ -1,25 +1,373 @@
-use clap::Parser;
+use horscan::significance::ShuffleMode;
+use horscan::window::WindowSize;
+use horscan::{MatrixBackend, Region, ValidationMode};
+use clap::{ArgAction, Parser, Subcommand};
 
 /// Program that processes command line arguments
 #[derive(Parser)]
 #[command(name = "HORSCAN")]
 #[command(version = "1.0")]
 #[command(about = "Command line tool for processing genome data", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     /// Input file
-    #[arg(short, long,  help = "source bed path")]
+    #[arg(short, long, required = true, help = "source bed path")]
+    pub source: Option<String>,
+    #[arg(short, long, required = true, help = "target bed path")]
+    pub target: Option<String>,
+    /// Output file
+    #[arg(short, long, required = true, help = "output alignment file prefix")]
+    pub output: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "temp file prefix for checkpoints; rerunning with the same prefix resumes an interrupted run"
+    )]
+    pub prefix: Option<String>,
+    #[arg(
+        long,
+        value_name = "memory|mmap",
+        default_value = "memory",
+        help = "keep the score matrix in memory or in a memory-mapped file next to --prefix (or in the system temp directory)"
+    )]
+    pub matrix: MatrixBackend,
+    /// Score matrix export
+    #[arg(long, help = "write the score matrix to <output>.score.npy")]
+    pub dump_matrix: bool,
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "dump_matrix",
+        help = "downsample the dumped matrix so that no dimension exceeds N (block maximum)"
+    )]
+    pub dump_max_size: Option<usize>,
+    /// HORSCAN params
+    #[arg(
+        short,
+        long,
+        value_parser,
+        help = "scores MATCH MISMATCH GAP, penalties as positive values",
+        num_args = 1..
+    )]
+    pub mode: Vec<i32>,
+    /// Run configuration
+    #[arg(short, long, help = "TOML or YAML run configuration file")]
+    pub config: Option<String>,
+    #[arg(
+        long,
+        help = "named scoring preset: default, haplotype, cross-species, assembly-qc"
+    )]
+    pub preset: Option<String>,
+    /// Region restriction
+    #[arg(long, value_name = "CHR:START-END", help = "only align source monomers overlapping this region")]
+    pub source_region: Option<Region>,
+    #[arg(long, value_name = "CHR:START-END", help = "only align target monomers overlapping this region")]
+    pub target_region: Option<Region>,
+    /// Input validation
+    #[arg(
+        long,
+        value_name = "off|strict|lenient",
+        help = "check input BEDs; strict fails on any problem, lenient repairs what it can [default: strict]"
+    )]
+    pub validate: Option<ValidationMode>,
+    /// Label normalisation
+    #[arg(
+        long,
+        value_name = "TSV",
+        help = "rename monomer labels of both BEDs after reading (columns: from, to)"
+    )]
+    pub label_map: Option<String>,
+    /// HOR phase
+    #[arg(
+        long,
+        help = "detect the HOR phase of both arrays and do not charge leading gaps of up to one unit length"
+    )]
+    pub phase: bool,
+    /// Significance
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help = "realign N times with shuffled target labels (the source is left as is) and report an empirical p-value and z-score for the whole alignment; rejected when source and target share a sequence"
+    )]
+    pub permutations: usize,
+    #[arg(
+        long,
+        value_name = "hor|kmer:K|monomer",
+        default_value = "hor",
+        help = "how target labels are shuffled: whole HOR copies, keeping the counts of all K-monomer words, or single monomers"
+    )]
+    pub shuffle: ShuffleMode,
+    #[arg(long, default_value_t = 1, help = "random seed for --permutations")]
+    pub seed: u64,
+    /// Logging
+    #[arg(short, long, global = true, action = ArgAction::Count, help = "more log output on stderr (-v debug, -vv trace)")]
+    pub verbose: u8,
+    #[arg(short, long, global = true, action = ArgAction::Count, help = "less log output on stderr (-q warnings, -qq errors only)")]
+    pub quiet: u8,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Align many source/target pairs listed in a TSV manifest
+    Batch(BatchArgs),
+    /// Align every pair of arrays and build a distance matrix and NJ tree
+    AllVsAll(AllVsAllArgs),
+    /// Check an existing .alignment against its BEDs and rescore it
+    Score(ScoreArgs),
+    /// Compare two alignments of the same source/target pair
+    Diff(DiffArgs),
+    /// Project source intervals onto the target through an alignment
+    Liftover(LiftoverArgs),
+    /// Infer HOR units from monomer labels and segment arrays into HOR copies
+    Hor(HorArgs),
+    /// Propose a label map between two annotations of the same assembly
+    LearnMap(LearnMapArgs),
+    /// Simulate a HOR array pair with known SV events
+    Simulate(SimulateArgs),
+    /// Compare SV calls with a truth table (precision, recall, F1)
+    Eval(EvalArgs),
+    /// Sliding-window identity and indel density along both arrays as bedGraph
+    Window(WindowArgs),
+}
+
+#[derive(clap::Args)]
+pub struct BatchArgs {
+    /// Manifest file
+    #[arg(
+        short = 'i',
+        long,
+        help = "manifest TSV: source, target, output prefix, optional mode"
+    )]
+    pub manifest: String,
+    #[arg(short = 'j', long, default_value_t = 1, help = "number of worker threads")]
+    pub threads: usize,
+    #[arg(
+        long,
+        default_value = "8G",
+        help = "global memory budget for running pairs (e.g. 512M, 16G)"
+    )]
+    pub memory: String,
+    /// Default HORSCAN params for pairs without a mode column
+    #[arg(short, long, value_parser, help = "run mode", num_args = 1..)]
+    pub mode: Vec<i32>,
+    #[arg(long, help = "named scoring preset for pairs without a mode column")]
+    pub preset: Option<String>,
+    #[arg(short, long, help = "TOML or YAML run configuration file")]
+    pub config: Option<String>,
+    #[arg(long, value_name = "off|strict|lenient", help = "input validation mode [default: strict]")]
+    pub validate: Option<ValidationMode>,
+}
+
+#[derive(clap::Args)]
+pub struct AllVsAllArgs {
+    /// Input files
+    #[arg(short, long, num_args = 1.., help = "monomer bed paths, one per haplotype")]
+    pub beds: Vec<String>,
+    #[arg(short, long, help = "file listing one bed path per line")]
+    pub list: Option<String>,
+    /// Output file
+    #[arg(
+        short,
+        long,
+        help = "output prefix for <prefix>.dist.tsv and <prefix>.nwk"
+    )]
+    pub output: String,
+    #[arg(short = 'j', long, default_value_t = 1, help = "number of worker threads")]
+    pub threads: usize,
+    /// HORSCAN params
+    #[arg(short, long, value_parser, help = "run mode", num_args = 1..)]
+    pub mode: Vec<i32>,
+    #[arg(long, help = "named scoring preset")]
+    pub preset: Option<String>,
+    #[arg(short, long, help = "TOML or YAML run configuration file")]
+    pub config: Option<String>,
+    #[arg(long, help = "detect the HOR phase of each pair, as in the main command")]
+    pub phase: bool,
+}
+
+#[derive(clap::Args)]
+pub struct ScoreArgs {
+    /// Input files
+    #[arg(short, long, help = "alignment file to check")]
+    pub alignment: String,
+    #[arg(short, long, help = "source bed path")]
     pub source: String,
-    #[arg(short, long,  help = "target bed path")]
+    #[arg(short, long, help = "target bed path")]
     pub target: String,
+    /// HORSCAN params
+    #[arg(short, long, value_parser, help = "scores MATCH MISMATCH GAP", num_args = 1..)]
+    pub mode: Vec<i32>,
+    #[arg(long, help = "named scoring preset")]
+    pub preset: Option<String>,
+    #[arg(short, long, help = "TOML or YAML run configuration file, as used for the alignment")]
+    pub config: Option<String>,
+    #[arg(long, value_name = "off|strict|lenient", help = "input validation mode, as used for the alignment [default: strict]")]
+    pub validate: Option<ValidationMode>,
+    /// Region restriction, as used for the alignment
+    #[arg(long, value_name = "CHR:START-END", help = "source region the alignment was restricted to")]
+    pub source_region: Option<Region>,
+    #[arg(long, value_name = "CHR:START-END", help = "target region the alignment was restricted to")]
+    pub target_region: Option<Region>,
+    #[arg(long, value_name = "TSV", help = "label map used for the alignment")]
+    pub label_map: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct DiffArgs {
+    /// Input files
+    #[arg(short, help = "first (reference) alignment file")]
+    pub a: String,
+    #[arg(short, help = "second alignment file")]
+    pub b: String,
+    /// Output file
+    #[arg(
+        short,
+        long,
+        help = "output prefix for <prefix>.diff.rows.tsv and <prefix>.diff.events.tsv"
+    )]
+    pub output: String,
+}
+
+#[derive(clap::Args)]
+pub struct LiftoverArgs {
+    /// Input files
+    #[arg(short, long, help = "alignment file")]
+    pub alignment: String,
+    #[arg(short, long, help = "BED of source intervals (chrom, start, end, optional name)")]
+    pub intervals: String,
     /// Output file
-    #[arg(short, long,  help = "output alignment file prefix")]
+    #[arg(short, long, help = "output prefix for <prefix>.lifted.bed")]
     pub output: String,
+}
 
-    #[arg(short, long, default_value = "None", help = "temp file prefix")]
-    pub prefix: String,
+#[derive(clap::Args)]
+pub struct HorArgs {
+    /// Input files
+    #[arg(short, long, help = "source bed path")]
+    pub source: String,
+    #[arg(short, long, help = "optional target bed path; aligns both arrays at HOR level")]
+    pub target: Option<String>,
+    /// Output file
+    #[arg(
+        short,
+        long,
+        help = "output prefix for <prefix>.units.tsv and <prefix>.hor.bed"
+    )]
+    pub output: String,
+    #[arg(long, default_value_t = 40, help = "longest HOR unit to look for, in monomers")]
+    pub max_period: usize,
     /// HORSCAN params
-    #[arg(short, long, value_parser, help = "run mode", num_args = 1..)]
+    #[arg(short, long, value_parser, help = "scores MATCH MISMATCH GAP for the HOR-level alignment", num_args = 1..)]
     pub mode: Vec<i32>,
+    #[arg(long, help = "named scoring preset")]
+    pub preset: Option<String>,
+    #[arg(short, long, help = "TOML or YAML run configuration file")]
+    pub config: Option<String>,
+    #[arg(long, value_name = "off|strict|lenient", help = "input validation mode [default: strict]")]
+    pub validate: Option<ValidationMode>,
+}
+
+#[derive(clap::Args)]
+pub struct LearnMapArgs {
+    /// Input files
+    #[arg(short, long, help = "annotation whose labels are kept")]
+    pub source: String,
+    #[arg(short, long, help = "annotation of the same assembly whose labels are mapped")]
+    pub target: String,
+    /// Output file
+    #[arg(short, long, help = "output prefix for <prefix>.labels.tsv")]
+    pub output: String,
+    #[arg(long, default_value = "strict", value_name = "off|strict|lenient", help = "input validation mode")]
+    pub validate: ValidationMode,
+}
+
+#[derive(clap::Args)]
+pub struct SimulateArgs {
+    /// HOR array
+    #[arg(short, long, value_delimiter = ',', required = true, help = "HOR unit as comma-separated monomer labels, e.g. A,B,C,D")]
+    pub unit: Vec<String>,
+    #[arg(short = 'n', long, default_value_t = 100, help = "number of HOR copies in the source array")]
+    pub copies: usize,
+    /// Event rates
+    #[arg(long, default_value_t = 0.01, help = "tandem expansion rate per HOR copy")]
+    pub expansion_rate: f64,
+    #[arg(long, default_value_t = 0.01, help = "contraction rate per HOR copy")]
+    pub contraction_rate: f64,
+    #[arg(long, default_value_t = 0.002, help = "substitution rate per monomer")]
+    pub substitution_rate: f64,
+    #[arg(long, default_value_t = 0.01, help = "partial-HOR insertion/deletion rate per HOR copy")]
+    pub indel_rate: f64,
+    #[arg(long, default_value_t = 0.005, help = "inversion rate per HOR copy")]
+    pub inversion_rate: f64,
+    #[arg(long, default_value_t = 3, help = "maximum number of copies gained or lost in one expansion or contraction")]
+    pub max_copies: usize,
+    /// BED layout
+    #[arg(long, default_value_t = 171, help = "distance between monomer starts (bp)")]
+    pub monomer_length: i32,
+    #[arg(long, default_value = "sim#source", help = "sample name of the source array")]
+    pub source_name: String,
+    #[arg(long, default_value = "sim#target", help = "sample name of the target array")]
+    pub target_name: String,
+    #[arg(long, default_value_t = 1, help = "random seed")]
+    pub seed: u64,
+    /// Output file
+    #[arg(
+        short,
+        long,
+        help = "output prefix for <prefix>.source.bed, <prefix>.target.bed and <prefix>.truth.tsv"
+    )]
+    pub output: String,
+}
+
+#[derive(clap::Args)]
+pub struct EvalArgs {
+    /// Input files
+    #[arg(long, help = "truth table, as written by simulate (<prefix>.truth.tsv)")]
+    pub truth: String,
+    #[arg(short, long, conflicts_with = "calls", help = "alignment file to call events from")]
+    pub alignment: Option<String>,
+    #[arg(short, long, help = "event table (kind, source, start, end, target, start, end, monomers)")]
+    pub calls: Option<String>,
+    /// Matching
+    #[arg(long, default_value_t = 500, help = "maximum distance between breakpoints (bp)")]
+    pub max_distance: i32,
+    #[arg(long, default_value_t = 0.7, help = "minimum size similarity (smaller/larger monomer count)")]
+    pub size_similarity: f64,
+    /// Output file
+    #[arg(
+        short,
+        long,
+        help = "output prefix for <prefix>.eval.truth.tsv and <prefix>.eval.calls.tsv"
+    )]
+    pub output: String,
+}
+
+#[derive(clap::Args)]
+pub struct WindowArgs {
+    /// Input files
+    #[arg(short, long, help = "alignment file")]
+    pub alignment: String,
+    /// Windows
+    #[arg(
+        short,
+        long,
+        default_value = "20",
+        help = "window size in monomers (e.g. 20) or bases (e.g. 5000bp, 10kb)"
+    )]
+    pub window: WindowSize,
+    #[arg(long, help = "distance between window starts, in the same unit [default: the window size]")]
+    pub step: Option<WindowSize>,
+    /// Output file
+    #[arg(
+        short,
+        long,
+        help = "output prefix for <prefix>.<side>.identity.bedGraph and <prefix>.<side>.indel.bedGraph"
+    )]
+    pub output: String,
 }
 
 pub fn parse_args() -> Args {
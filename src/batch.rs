@@ -0,0 +1,327 @@
+use crate::horscan::{horscan_main, RunOptions};
+use crate::io::{read_data_lines, split_columns, HorScanError, Mode};
+use crate::logging::{set_progress_enabled, start_capture, take_captured, write_captured};
+use crate::matrix::{MatrixBackend, ScoreMatrix};
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// manifest 中的一行：一个 source/target 比对任务
+#[derive(Clone, Debug)]
+pub struct BatchPair {
+    pub line: usize,
+    pub source: String,
+    pub target: String,
+    pub output: String,
+    pub mode: Mode,
+}
+
+#[derive(Clone, Debug)]
+struct PairResult {
+    memory: u64,
+    elapsed: Duration,
+    status: Result<(), String>,
+}
+
+/// 内存预算：运行中任务的预估内存之和不超过 budget
+struct MemoryBudget {
+    budget: u64,
+    used: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    fn acquire(&self, need: u64) {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        // 超过总预算的任务只能在没有其他任务运行时单独执行
+        while *used > 0 && *used + need > self.budget {
+            used = self.released.wait(used).unwrap_or_else(|e| e.into_inner());
+        }
+        *used += need;
+    }
+
+    fn release(&self, need: u64) {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        *used -= need;
+        self.released.notify_all();
+    }
+}
+
+/// 解析内存大小，支持 K/M/G/T 后缀（1024 进制），无后缀时单位为字节
+pub fn parse_memory_size(s: &str) -> Result<u64, HorScanError> {
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().find(|(_, c)| c.is_ascii_alphabetic()) {
+        Some((idx, _)) => (&s[..idx], s[idx..].to_ascii_uppercase()),
+        None => (s, String::new()),
+    };
+    let shift = match unit.trim_end_matches(['B', 'I']) {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => {
+            return Err(HorScanError::InvalidValue(format!(
+                "unknown memory unit in '{}'",
+                s
+            )))
+        }
+    };
+    let value: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| HorScanError::InvalidValue(format!("invalid memory size '{}'", s)))?;
+    Ok((value * (1u64 << shift) as f64) as u64)
+}
+
+fn format_memory_size(bytes: u64) -> String {
+    let units = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", value, units[unit])
+}
+
+/// 解析 mode 列，例如 `10,4,2` 或 `10 4 2`
+fn parse_mode_field(s: &str) -> Result<Mode, HorScanError> {
+    let values = s
+        .split([',', ' '])
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            v.parse::<i32>()
+                .map_err(|_| HorScanError::InvalidValue(format!("invalid mode value '{}'", v)))
+        })
+        .collect::<Result<Vec<i32>, HorScanError>>()?;
+    if values.len() != 3 {
+        return Err(HorScanError::InvalidValue(format!(
+            "mode '{}' needs MATCH MISMATCH GAP",
+            s
+        )));
+    }
+    Ok(Mode::from_params(&values))
+}
+
+/// 读取 batch manifest，每行为 source、target、output prefix 以及可选的 mode
+pub fn read_manifest<P: AsRef<Path>>(
+    path: P,
+    default_mode: &Mode,
+) -> Result<Vec<BatchPair>, HorScanError> {
+    let name = path.as_ref().display().to_string();
+    let mut pairs = Vec::new();
+
+    for (line_number, line) in read_data_lines(&path)? {
+        let parts = split_columns(&line, 3, &name, line_number)?;
+        let mode = match parts.get(3).map(|s| s.trim()) {
+            Some(field) if !field.is_empty() => {
+                parse_mode_field(field).map_err(|e| HorScanError::Parse {
+                    path: name.clone(),
+                    line: line_number,
+                    column: 4,
+                    reason: match e {
+                        HorScanError::InvalidValue(reason) => reason,
+                        other => other.to_string(),
+                    },
+                })?
+            }
+            _ => default_mode.clone(),
+        };
+        pairs.push(BatchPair {
+            line: line_number,
+            source: parts[0].to_string(),
+            target: parts[1].to_string(),
+            output: parts[2].to_string(),
+            mode,
+        });
+    }
+    Ok(pairs)
+}
+
+/// 预估一个任务的峰值内存：主要是 (n+1) x (m+1) 的打分矩阵，按 backend 的存储方式计算
+pub fn estimate_pair_memory(pair: &BatchPair, backend: MatrixBackend) -> Result<u64, HorScanError> {
+    let source_len = read_data_lines(&pair.source)?.len() + 1;
+    let target_len = read_data_lines(&pair.target)?.len() + 1;
+    Ok(ScoreMatrix::bytes_for(source_len, target_len, backend))
+}
+
+fn run_pair(pair: &BatchPair, options: &RunOptions) -> Result<(), String> {
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        horscan_main(
+            pair.source.clone(),
+            pair.target.clone(),
+            pair.output.clone(),
+            pair.mode.clone(),
+            options.clone(),
+        )
+    }));
+    match outcome {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(format!("panicked: {}", message))
+        }
+    }
+}
+
+fn print_summary(pairs: &[BatchPair], results: &[Option<PairResult>]) {
+    println!("#line\tstatus\tsource\ttarget\toutput\test_memory\tseconds\tmessage");
+    for (pair, result) in pairs.iter().zip(results) {
+        let (status, memory, seconds, message) = match result {
+            Some(r) => match &r.status {
+                Ok(()) => ("OK", r.memory, r.elapsed.as_secs_f64(), String::new()),
+                Err(e) => ("FAILED", r.memory, r.elapsed.as_secs_f64(), e.clone()),
+            },
+            None => ("SKIPPED", 0, 0.0, String::new()),
+        };
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:.2}\t{}",
+            pair.line,
+            status,
+            pair.source,
+            pair.target,
+            pair.output,
+            format_memory_size(memory),
+            seconds,
+            message
+        );
+    }
+}
+
+/// 在线程池中并行执行 manifest 中的所有任务，单个任务失败不会中止整个批次
+pub fn run_batch(
+    manifest: &str,
+    threads: usize,
+    memory_budget: u64,
+    default_mode: &Mode,
+    options: &RunOptions,
+) -> Result<(), HorScanError> {
+    let pairs = read_manifest(manifest, default_mode)?;
+    let budget = MemoryBudget {
+        budget: memory_budget,
+        used: Mutex::new(0),
+        released: Condvar::new(),
+    };
+    let threads = threads.max(1).min(pairs.len().max(1));
+    if threads > 1 {
+        set_progress_enabled(false);
+    }
+    log::info!(
+        "batch: {} pairs, {} threads, memory budget {}",
+        pairs.len(),
+        threads,
+        format_memory_size(budget.budget)
+    );
+
+    let queue: Mutex<VecDeque<usize>> = Mutex::new((0..pairs.len()).collect());
+    let mut results: Vec<Option<PairResult>> = vec![None; pairs.len()];
+    let (sender, receiver) = mpsc::channel::<(usize, PairResult, Vec<String>)>();
+    // 多线程时工作线程的日志先缓存，任务结束后由主线程整块输出，避免不同任务的日志交错
+    let capture = threads > 1;
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (queue, pairs, budget) = (&queue, &pairs, &budget);
+            scope.spawn(move || loop {
+                let next = queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+                let Some(idx) = next else { break };
+                let pair = &pairs[idx];
+                if capture {
+                    start_capture();
+                }
+                let started = Instant::now();
+                let result = match estimate_pair_memory(pair, options.matrix_backend) {
+                    Ok(memory) => {
+                        if memory > budget.budget {
+                            log::warn!(
+                                "batch: line {} needs ~{} (over budget), running it alone",
+                                pair.line,
+                                format_memory_size(memory)
+                            );
+                        }
+                        budget.acquire(memory);
+                        let status = run_pair(pair, options);
+                        budget.release(memory);
+                        PairResult {
+                            memory,
+                            elapsed: started.elapsed(),
+                            status,
+                        }
+                    }
+                    Err(e) => PairResult {
+                        memory: 0,
+                        elapsed: started.elapsed(),
+                        status: Err(e.to_string()),
+                    },
+                };
+                let lines = if capture { take_captured() } else { Vec::new() };
+                if sender.send((idx, result, lines)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        for (idx, result, lines) in receiver {
+            write_captured(&lines);
+            match &result.status {
+                Ok(()) => log::info!(
+                    "batch: line {} done in {:.1}s",
+                    pairs[idx].line,
+                    result.elapsed.as_secs_f64()
+                ),
+                Err(e) => log::error!("batch: line {} failed: {}", pairs[idx].line, e),
+            }
+            results[idx] = Some(result);
+        }
+    });
+
+    print_summary(&pairs, &results);
+    let failed = results
+        .iter()
+        .filter(|r| !matches!(r, Some(PairResult { status: Ok(()), .. })))
+        .count();
+    if failed > 0 {
+        return Err(HorScanError::PairsFailed {
+            failed,
+            total: pairs.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_memory_size() -> Result<(), HorScanError> {
+        assert_eq!(parse_memory_size("1024")?, 1024);
+        assert_eq!(parse_memory_size("512M")?, 512 << 20);
+        assert_eq!(parse_memory_size("2G")?, 2 << 30);
+        assert_eq!(parse_memory_size("1.5GiB")?, 3 << 29);
+        assert!(parse_memory_size("3X").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_mode_field() -> Result<(), HorScanError> {
+        let mode = parse_mode_field("10,4,2")?;
+        assert_eq!(
+            (mode.match_score, mode.mismatch_score, mode.gap_score),
+            (10, -4, -2)
+        );
+        assert!(parse_mode_field("10,4").is_err());
+        Ok(())
+    }
+}
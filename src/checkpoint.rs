@@ -0,0 +1,305 @@
+use crate::io::{
+    read_blast_file, save_blast_file, write_output, HorScanError, Mode, MonAlignmentRow, MonRow,
+};
+use crate::matrix::ScoreMatrix;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// 每个检查点块至少包含的矩阵单元数，块写完后才落盘同步
+const CHECKPOINT_CELLS: usize = 1 << 22;
+
+/// 将单体标签映射为整数编号，动态规划只比较编号
+pub fn intern_labels(source: &[MonRow], target: &[MonRow]) -> (Vec<u32>, Vec<u32>) {
+    let mut ids: HashMap<&str, u32> = HashMap::new();
+    let mut interned = [Vec::with_capacity(source.len()), Vec::with_capacity(target.len())];
+    for (bed, out) in [source, target].into_iter().zip(interned.iter_mut()) {
+        for row in bed {
+            let next = ids.len() as u32;
+            out.push(*ids.entry(row.mon.as_str()).or_insert(next));
+        }
+    }
+    let [source_ids, target_ids] = interned;
+    (source_ids, target_ids)
+}
+
+/// 64 位 FNV-1a 哈希，用于判断检查点是否属于同一次比对（不依赖标准库哈希的实现细节）
+struct Fingerprint(u64);
+
+impl Fingerprint {
+    fn new() -> Fingerprint {
+        Fingerprint(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        // 字段分隔符，避免 "ab","c" 与 "a","bc" 相同
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    fn write_bed(&mut self, bed: &[MonRow]) {
+        self.write(&(bed.len() as u64).to_le_bytes());
+        for row in bed {
+            self.write(row.sample.as_bytes());
+            self.write(&row.start.to_le_bytes());
+            self.write(&row.end.to_le_bytes());
+            self.write(row.mon.as_bytes());
+        }
+    }
+}
+
+/// `--prefix` 下的中间文件：
+///
+/// * `<prefix>.state`：本次比对的指纹，指纹不同的旧文件会被清除
+/// * `<prefix>.source.bed` / `<prefix>.target.bed`：排序、截取后实际参与比对的单体，第 5 列为标签编号
+/// * `<prefix>.dp`：按行块追加的得分矩阵（小端 i32），中断后从最后一个完整的行块继续
+/// * `<prefix>.raw.alignment`：优化前的比对结果，存在时直接跳过动态规划
+pub struct TempStore {
+    prefix: String,
+}
+
+impl TempStore {
+    /// 打开临时目录；输入或打分参数与已有检查点不一致时丢弃旧的检查点
+    pub fn open(
+        prefix: &str,
+        source: &[MonRow],
+        target: &[MonRow],
+        mode: &Mode,
+        free_gaps: usize,
+    ) -> Result<TempStore, HorScanError> {
+        let store = TempStore {
+            prefix: prefix.to_string(),
+        };
+        if let Some(parent) = Path::new(&store.path("state")).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|source| HorScanError::Write {
+                    path: parent.display().to_string(),
+                    source,
+                })?;
+            }
+        }
+
+        let mut fingerprint = Fingerprint::new();
+        fingerprint.write_bed(source);
+        fingerprint.write_bed(target);
+        for score in [mode.match_score, mode.mismatch_score, mode.gap_score] {
+            fingerprint.write(&score.to_le_bytes());
+        }
+        fingerprint.write(&(free_gaps as u64).to_le_bytes());
+        let state = format!(
+            "fingerprint\t{:016x}\nsource\t{}\ntarget\t{}\n",
+            fingerprint.0,
+            source.len(),
+            target.len()
+        );
+
+        let state_path = store.path("state");
+        match fs::read_to_string(&state_path) {
+            Ok(old) if old == state => {
+                log::info!("resuming from temp files at {}", prefix);
+            }
+            old => {
+                if old.is_ok() {
+                    log::warn!(
+                        "temp files at {} belong to a different run, starting over",
+                        prefix
+                    );
+                }
+                for name in ["dp", "raw.alignment"] {
+                    store.remove(name);
+                }
+                write_output(&state_path, |writer| write!(writer, "{}", state))?;
+                let (source_ids, target_ids) = intern_labels(source, target);
+                store.save_interned("source.bed", source, &source_ids)?;
+                store.save_interned("target.bed", target, &target_ids)?;
+            }
+        }
+        Ok(store)
+    }
+
+    fn path(&self, name: &str) -> String {
+        format!("{}.{}", self.prefix, name)
+    }
+
+    fn remove(&self, name: &str) {
+        // 文件不存在时忽略
+        let _ = fs::remove_file(self.path(name));
+    }
+
+    fn save_interned(&self, name: &str, bed: &[MonRow], ids: &[u32]) -> Result<(), HorScanError> {
+        write_output(self.path(name), |writer| {
+            for (row, id) in bed.iter().zip(ids) {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{}",
+                    row.sample, row.start, row.end, row.mon, id
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// 将已完成的得分矩阵行读回 matrix，返回可以继续填充的起始行
+    ///
+    /// 只保留完整的 band 行块（或整个矩阵），文件截断到保留的行数，之后从这里继续追加
+    pub fn restore_dp_rows(
+        &self,
+        matrix: &mut ScoreMatrix,
+        band: usize,
+    ) -> Result<usize, HorScanError> {
+        let path = self.path("dp");
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => return Ok(0),
+        };
+        let mut reader = BufReader::new(file);
+        let mut bytes = vec![0u8; matrix.cols() * 4];
+        let mut values = vec![0i32; matrix.cols()];
+        let mut read = 0;
+        while read < matrix.rows() && reader.read_exact(&mut bytes).is_ok() {
+            for (value, cell) in values.iter_mut().zip(bytes.chunks_exact(4)) {
+                *value = i32::from_le_bytes([cell[0], cell[1], cell[2], cell[3]]);
+            }
+            matrix.set_row(read, &values);
+            read += 1;
+        }
+        let keep = if read == matrix.rows() {
+            read
+        } else {
+            read / band.max(1) * band.max(1)
+        };
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_len((keep * bytes.len()) as u64))
+            .map_err(|source| HorScanError::Write {
+                path: path.clone(),
+                source,
+            })?;
+        Ok(keep)
+    }
+
+    /// Mmap 后端的得分矩阵文件
+    pub fn matrix_path(&self) -> String {
+        self.path("matrix")
+    }
+
+    /// 以追加方式打开得分矩阵检查点
+    pub fn dp_writer(&self, width: usize) -> Result<DpCheckpoint, HorScanError> {
+        let path = self.path("dp");
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|source| HorScanError::Write {
+                path: path.clone(),
+                source,
+            })?;
+        Ok(DpCheckpoint {
+            path,
+            writer: BufWriter::new(file),
+            block_rows: (CHECKPOINT_CELLS / width.max(1)).max(1),
+            pending: 0,
+        })
+    }
+
+    /// 回溯完成后保存优化前的比对，并删除已不再需要的得分矩阵检查点
+    pub fn save_raw_alignment(&self, rows: &[MonAlignmentRow]) -> Result<(), HorScanError> {
+        save_blast_file(rows, self.path("raw.alignment"))?;
+        self.remove("dp");
+        Ok(())
+    }
+
+    /// 读取上次保存的优化前比对
+    pub fn load_raw_alignment(&self) -> Result<Option<Vec<MonAlignmentRow>>, HorScanError> {
+        let path = self.path("raw.alignment");
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+        read_blast_file(path).map(Some)
+    }
+}
+
+/// 得分矩阵检查点的写入端，每写满一个行块就刷新并同步到磁盘
+pub struct DpCheckpoint {
+    path: String,
+    writer: BufWriter<File>,
+    block_rows: usize,
+    pending: usize,
+}
+
+impl DpCheckpoint {
+    pub fn push_row(&mut self, row: &[i32]) -> Result<(), HorScanError> {
+        let mut result = row
+            .iter()
+            .try_for_each(|cell| self.writer.write_all(&cell.to_le_bytes()));
+        self.pending += 1;
+        if result.is_ok() && self.pending >= self.block_rows {
+            result = self.sync();
+        }
+        result.map_err(|source| HorScanError::Write {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    pub fn finish(mut self) -> Result<(), HorScanError> {
+        self.sync().map_err(|source| HorScanError::Write {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.pending = 0;
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{bed, TestDir};
+    use crate::matrix::MatrixBackend;
+
+    #[test]
+    fn test_intern_labels() {
+        let (source, target) = intern_labels(&bed("chrX", "ABA"), &bed("chrX", "CAB"));
+        assert_eq!(source, [0, 1, 0]);
+        assert_eq!(target, [2, 0, 1]);
+    }
+
+    #[test]
+    fn test_dp_checkpoint_resume() -> Result<(), HorScanError> {
+        let dir = TestDir::new("checkpoint");
+        let prefix = dir.path("run").display().to_string();
+        let mode = Mode::default_mode();
+        let store = TempStore::open(&prefix, &bed("chrX", "AB"), &bed("chrX", "AC"), &mode, 0)?;
+        let mut writer = store.dp_writer(2)?;
+        for row in [[0, -2], [-2, 4], [-4, 2]] {
+            writer.push_row(&row)?;
+        }
+        writer.finish()?;
+        // 模拟中断：最后一行只写了一半
+        let mut file = OpenOptions::new().append(true).open(store.path("dp"))?;
+        file.write_all(&7i32.to_le_bytes())?;
+
+        let store = TempStore::open(&prefix, &bed("chrX", "AB"), &bed("chrX", "AC"), &mode, 0)?;
+        let mut matrix = ScoreMatrix::new(4, 2, MatrixBackend::Memory, &store.matrix_path())?;
+        // 只保留完整的两行块
+        assert_eq!(store.restore_dp_rows(&mut matrix, 2)?, 2);
+        assert_eq!(matrix.row(1), [-2, 4]);
+        assert_eq!(fs::metadata(store.path("dp"))?.len(), 16);
+
+        // 输入改变后旧的检查点被丢弃
+        let store = TempStore::open(&prefix, &bed("chrX", "AB"), &bed("chrX", "AD"), &mode, 0)?;
+        assert_eq!(store.restore_dp_rows(&mut matrix, 2)?, 0);
+        Ok(())
+    }
+}
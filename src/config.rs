@@ -0,0 +1,337 @@
+use crate::io::{write_output, HorScanError, Mode};
+use crate::validate::ValidationMode;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// 内置的打分预设：(名称, match, mismatch 罚分, gap 罚分, 说明)
+pub const PRESETS: [(&str, i32, i32, i32, &str); 4] = [
+    ("default", 4, 5, 2, "built-in defaults"),
+    (
+        "haplotype",
+        10,
+        4,
+        2,
+        "haplotypes of the same species, high identity expected",
+    ),
+    (
+        "cross-species",
+        4,
+        2,
+        1,
+        "diverged arrays, cheap mismatches and gaps",
+    ),
+    (
+        "assembly-qc",
+        10,
+        10,
+        5,
+        "two assemblies of the same genome, every difference is penalised",
+    ),
+];
+
+/// 打分参数，罚分以正数书写，实际打分时取负
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ScoringConfig {
+    #[serde(rename = "match")]
+    pub match_score: i32,
+    pub mismatch: i32,
+    pub gap: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AlignmentConfig {
+    /// 输入校验模式 off/strict/lenient
+    pub validate: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct OptimizerConfig {
+    /// 是否在动态规划回溯后运行优化
+    pub enabled: bool,
+}
+
+/// 一次运行的完整配置，即写出的 effective config
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RunConfig {
+    pub preset: String,
+    pub scoring: ScoringConfig,
+    pub alignment: AlignmentConfig,
+    pub optimizer: OptimizerConfig,
+}
+
+// 配置文件中所有字段均可省略，省略的字段取预设值
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScoringFile {
+    #[serde(rename = "match")]
+    match_score: Option<i32>,
+    mismatch: Option<i32>,
+    gap: Option<i32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AlignmentFile {
+    validate: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OptimizerFile {
+    enabled: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    preset: Option<String>,
+    #[serde(default)]
+    scoring: ScoringFile,
+    #[serde(default)]
+    alignment: AlignmentFile,
+    #[serde(default)]
+    optimizer: OptimizerFile,
+}
+
+impl RunConfig {
+    /// 由预设名构建配置
+    pub fn from_preset(name: &str) -> Result<RunConfig, HorScanError> {
+        let (preset, match_score, mismatch, gap, _) = PRESETS
+            .iter()
+            .find(|preset| preset.0 == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = PRESETS.iter().map(|preset| preset.0).collect();
+                HorScanError::InvalidValue(format!(
+                    "unknown preset '{}', expected one of {}",
+                    name,
+                    names.join(", ")
+                ))
+            })?;
+        Ok(RunConfig {
+            preset: preset.to_string(),
+            scoring: ScoringConfig {
+                match_score: *match_score,
+                mismatch: *mismatch,
+                gap: *gap,
+            },
+            alignment: AlignmentConfig {
+                validate: ValidationMode::default().to_string(),
+            },
+            optimizer: OptimizerConfig { enabled: true },
+        })
+    }
+
+    /// 读取 TOML 或 YAML（按扩展名 .yaml/.yml 判断）配置文件；
+    /// preset 参数优先于文件中的 preset，文件中给出的字段覆盖预设值
+    pub fn load<P: AsRef<Path>>(path: P, preset: Option<&str>) -> Result<RunConfig, HorScanError> {
+        let name = path.as_ref().display().to_string();
+        let text = std::fs::read_to_string(&path).map_err(|source| HorScanError::Read {
+            path: name.clone(),
+            source,
+        })?;
+        let yaml = matches!(
+            path.as_ref().extension().and_then(|e| e.to_str()),
+            Some("yaml") | Some("yml")
+        );
+        let file: ConfigFile = if yaml {
+            serde_yaml::from_str(&text).map_err(|e| {
+                let (line, column) = e
+                    .location()
+                    .map(|l| (l.line(), l.column()))
+                    .unwrap_or((0, 0));
+                HorScanError::Parse {
+                    path: name.clone(),
+                    line,
+                    column,
+                    reason: e.to_string(),
+                }
+            })?
+        } else {
+            toml::from_str(&text).map_err(|e| {
+                let (line, column) = e
+                    .span()
+                    .map(|span| line_column(&text, span.start))
+                    .unwrap_or((0, 0));
+                HorScanError::Parse {
+                    path: name.clone(),
+                    line,
+                    column,
+                    reason: e.message().to_string(),
+                }
+            })?
+        };
+
+        let preset = preset
+            .map(|p| p.to_string())
+            .or(file.preset)
+            .unwrap_or_else(|| "default".to_string());
+        let mut config = RunConfig::from_preset(&preset)?;
+        let scoring = &mut config.scoring;
+        scoring.match_score = file.scoring.match_score.unwrap_or(scoring.match_score);
+        scoring.mismatch = file.scoring.mismatch.unwrap_or(scoring.mismatch);
+        scoring.gap = file.scoring.gap.unwrap_or(scoring.gap);
+        if let Some(validate) = file.alignment.validate {
+            config.alignment.validate = validate;
+        }
+        if let Some(enabled) = file.optimizer.enabled {
+            config.optimizer.enabled = enabled;
+        }
+        config.check()?;
+        Ok(config)
+    }
+
+    /// 用命令行 `--mode MATCH MISMATCH GAP` 覆盖打分参数
+    pub fn apply_mode_params(&mut self, params: &[i32]) -> Result<(), HorScanError> {
+        if params.is_empty() {
+            return Ok(());
+        }
+        if params.len() != 3 {
+            return Err(HorScanError::InvalidValue(format!(
+                "--mode needs exactly 3 values MATCH MISMATCH GAP, got {}",
+                params.len()
+            )));
+        }
+        self.scoring = ScoringConfig {
+            match_score: params[0],
+            mismatch: params[1],
+            gap: params[2],
+        };
+        self.check()
+    }
+
+    /// 检查配置取值是否合法
+    pub fn check(&self) -> Result<(), HorScanError> {
+        if self.scoring.mismatch < 0 || self.scoring.gap < 0 {
+            return Err(HorScanError::InvalidValue(format!(
+                "mismatch ({}) and gap ({}) are penalties and must be given as positive values",
+                self.scoring.mismatch, self.scoring.gap
+            )));
+        }
+        self.validation()?;
+        Ok(())
+    }
+
+    /// 打分参数，罚分取负
+    pub fn mode(&self) -> Mode {
+        Mode::from_params(&[
+            self.scoring.match_score,
+            self.scoring.mismatch,
+            self.scoring.gap,
+        ])
+    }
+
+    pub fn validation(&self) -> Result<ValidationMode, HorScanError> {
+        self.alignment.validate.parse()
+    }
+
+    pub fn to_toml(&self) -> Result<String, HorScanError> {
+        toml::to_string(self)
+            .map_err(|e| HorScanError::InvalidValue(format!("cannot serialise config: {}", e)))
+    }
+
+    /// 将 effective config 以 TOML 写出
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), HorScanError> {
+        let text = self.to_toml()?;
+        write_output(path, |writer| {
+            writeln!(
+                writer,
+                "# HORSCAN effective config; mismatch and gap are penalties (scored as negative)"
+            )?;
+            write!(writer, "{}", text)
+        })
+    }
+}
+
+/// 由命令行的 `--config`、`--preset`、`--mode` 与 `--validate` 得到配置，
+/// 优先级：预设 < 配置文件 < 命令行参数
+pub fn resolve_config(
+    path: Option<&str>,
+    preset: Option<&str>,
+    params: &[i32],
+    validate: Option<ValidationMode>,
+) -> Result<RunConfig, HorScanError> {
+    let mut config = match path {
+        Some(path) => RunConfig::load(path, preset)?,
+        None => RunConfig::from_preset(preset.unwrap_or("default"))?,
+    };
+    config.apply_mode_params(params)?;
+    if let Some(validate) = validate {
+        config.alignment.validate = validate.to_string();
+    }
+    config.check()?;
+    Ok(config)
+}
+
+/// 字节偏移转换为从 1 开始的行号和列号
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+
+    fn write_temp(dir: &TestDir, name: &str, text: &str) -> std::path::PathBuf {
+        let path = dir.path(name);
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_config_toml_overrides_preset() -> Result<(), HorScanError> {
+        let dir = TestDir::new("config_toml");
+        let path = write_temp(
+            &dir,
+            "config.toml",
+            "preset = \"haplotype\"\n[scoring]\ngap = 3\n[optimizer]\nenabled = false\n",
+        );
+        let config = RunConfig::load(&path, None)?;
+        assert_eq!(config.preset, "haplotype");
+        assert_eq!(config.scoring.match_score, 10);
+        assert_eq!(config.mode().gap_score, -3);
+        assert!(!config.optimizer.enabled);
+
+        // 写出的 effective config 可以被重新读入
+        let saved = dir.path("saved.toml");
+        config.save(&saved)?;
+        assert_eq!(RunConfig::load(&saved, None)?, config);
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_yaml_and_errors() -> Result<(), HorScanError> {
+        let dir = TestDir::new("config_yaml");
+        let path = write_temp(
+            &dir,
+            "config.yaml",
+            "scoring:\n  match: 7\nalignment:\n  validate: strict\n",
+        );
+        let config = RunConfig::load(&path, Some("assembly-qc"))?;
+        assert_eq!(config.preset, "assembly-qc");
+        assert_eq!(config.scoring.match_score, 7);
+        assert_eq!(config.validation()?, ValidationMode::Strict);
+
+        let bad = write_temp(&dir, "bad.toml", "[scoring]\nmatchh = 1\n");
+        assert!(matches!(
+            RunConfig::load(&bad, None),
+            Err(HorScanError::Parse { line: 2, .. })
+        ));
+        // 命令行参数覆盖配置文件
+        let path = path.display().to_string();
+        let config = resolve_config(Some(&path), None, &[5, 1, 1], Some(ValidationMode::Off))?;
+        assert_eq!(
+            (config.scoring.match_score, config.validation()?),
+            (5, ValidationMode::Off)
+        );
+        let mut config = RunConfig::from_preset("default")?;
+        assert!(config.apply_mode_params(&[10, 4]).is_err());
+        assert!(config.apply_mode_params(&[10, -4, 2]).is_err());
+        assert!(RunConfig::from_preset("nope").is_err());
+        Ok(())
+    }
+}
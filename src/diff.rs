@@ -0,0 +1,337 @@
+use crate::io::{read_blast_file, write_output, HorScanError, MonAlignmentRow};
+use crate::optimize::TypeCount;
+use crate::sv::{call_events, SvEvent};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+
+/// 单体的标识：样本、起点、终点、标签
+type MonKey = (String, i32, i32, String);
+
+fn source_key(row: &MonAlignmentRow) -> Option<MonKey> {
+    (row.source_mon != "-").then(|| {
+        (
+            row.source.clone(),
+            row.source_start,
+            row.source_end,
+            row.source_mon.clone(),
+        )
+    })
+}
+
+fn target_key(row: &MonAlignmentRow) -> Option<MonKey> {
+    (row.target_mon != "-").then(|| {
+        (
+            row.target.clone(),
+            row.target_start,
+            row.target_end,
+            row.target_mon.clone(),
+        )
+    })
+}
+
+fn describe(key: &Option<MonKey>) -> String {
+    match key {
+        Some((sample, start, end, mon)) => format!("{}:{}-{} {}", sample, start, end, mon),
+        None => "-".to_string(),
+    }
+}
+
+type SideKey = fn(&MonAlignmentRow) -> Option<MonKey>;
+
+/// 一侧每个单体在比对中另一侧的对应单体（空位为 None）及比对类型，按坐标排序
+fn partners(
+    rows: &[MonAlignmentRow],
+    own: SideKey,
+    other: SideKey,
+) -> BTreeMap<MonKey, (Option<MonKey>, String)> {
+    rows.iter()
+        .filter_map(|row| Some((own(row)?, (other(row), row.align_type.clone()))))
+        .collect()
+}
+
+/// 一个单体在两个比对中的对应关系不同
+#[derive(Clone, Debug)]
+pub struct PartnerChange {
+    /// 单体所在的一侧：`source` 或 `target`
+    pub side: &'static str,
+    pub monomer: MonKey,
+    pub a_partner: Option<MonKey>,
+    pub a_type: String,
+    pub b_partner: Option<MonKey>,
+    pub b_type: String,
+}
+
+/// 事件在两个比对之间的变化
+#[derive(Clone, Debug)]
+pub enum EventChange {
+    /// 只出现在 B 中
+    Appeared(SvEvent),
+    /// 只出现在 A 中
+    Vanished(SvEvent),
+    /// 两边都有重叠的同类型事件，但范围不同
+    Shifted(SvEvent, SvEvent),
+}
+
+/// 两个比对的差异及一致性统计
+#[derive(Clone, Debug)]
+pub struct AlignmentDiff {
+    pub changes: Vec<PartnerChange>,
+    pub events: Vec<EventChange>,
+    /// 两个比对中都出现的 source / target 单体数，以及其中对应关系改变的单体数
+    pub shared_source: usize,
+    pub shared_target: usize,
+    pub changed_source: usize,
+    pub changed_target: usize,
+    /// 只出现在其中一个比对中的 source/target 单体数，同一对序列的比对应为 0
+    pub unshared_monomers: usize,
+    /// 两边都有的 MTH/MIS 单体对数，以及两边单体对的并集大小
+    pub shared_pairs: usize,
+    pub union_pairs: usize,
+    pub unchanged_events: usize,
+    pub a_count: TypeCount,
+    pub b_count: TypeCount,
+}
+
+/// 比较一侧单体在两个比对中的对应关系，改变的记入 changes；返回 (共有单体数, 只在一边出现的单体数)
+fn compare_side(
+    a: &[MonAlignmentRow],
+    b: &[MonAlignmentRow],
+    side: &'static str,
+    own: SideKey,
+    other: SideKey,
+    changes: &mut Vec<PartnerChange>,
+) -> (usize, usize) {
+    let a_partners = partners(a, own, other);
+    let b_partners = partners(b, own, other);
+    let mut shared = 0;
+    for (key, (a_partner, a_type)) in &a_partners {
+        let Some((b_partner, b_type)) = b_partners.get(key) else {
+            continue;
+        };
+        shared += 1;
+        if a_partner != b_partner {
+            changes.push(PartnerChange {
+                side,
+                monomer: key.clone(),
+                a_partner: a_partner.clone(),
+                a_type: a_type.clone(),
+                b_partner: b_partner.clone(),
+                b_type: b_type.clone(),
+            });
+        }
+    }
+    (shared, a_partners.len() + b_partners.len() - 2 * shared)
+}
+
+/// 比较同一对序列的两个比对
+pub fn diff_alignments(a: &[MonAlignmentRow], b: &[MonAlignmentRow]) -> AlignmentDiff {
+    let mut changes = Vec::new();
+    let (shared_source, unshared_source) =
+        compare_side(a, b, "source", source_key, target_key, &mut changes);
+    let changed_source = changes.len();
+    let (shared_target, unshared_target) =
+        compare_side(a, b, "target", target_key, source_key, &mut changes);
+    let changed_target = changes.len() - changed_source;
+
+    let pairs = |rows: &[MonAlignmentRow]| -> HashSet<(MonKey, MonKey)> {
+        rows.iter()
+            .filter_map(|row| Some((source_key(row)?, target_key(row)?)))
+            .collect()
+    };
+    let (a_pairs, b_pairs) = (pairs(a), pairs(b));
+    let shared_pairs = a_pairs.intersection(&b_pairs).count();
+
+    let a_events = call_events(a);
+    let b_events = call_events(b);
+    let mut events = Vec::new();
+    let mut unchanged_events = 0;
+    for event in &a_events {
+        if b_events.contains(event) {
+            unchanged_events += 1;
+        } else if let Some(other) = b_events.iter().find(|other| event.overlaps(other)) {
+            events.push(EventChange::Shifted(event.clone(), other.clone()));
+        } else {
+            events.push(EventChange::Vanished(event.clone()));
+        }
+    }
+    for event in &b_events {
+        if !a_events.iter().any(|other| event.overlaps(other)) {
+            events.push(EventChange::Appeared(event.clone()));
+        }
+    }
+
+    AlignmentDiff {
+        changes,
+        events,
+        shared_source,
+        shared_target,
+        changed_source,
+        changed_target,
+        unshared_monomers: unshared_source + unshared_target,
+        shared_pairs,
+        union_pairs: a_pairs.len() + b_pairs.len() - shared_pairs,
+        unchanged_events,
+        a_count: TypeCount::from_rows(a),
+        b_count: TypeCount::from_rows(b),
+    }
+}
+
+fn fraction(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// diff 命令：写出 `<prefix>.diff.rows.tsv` 与 `<prefix>.diff.events.tsv`，并在标准输出打印摘要
+pub fn run_diff(a_path: &str, b_path: &str, output_prefix: &str) -> Result<(), HorScanError> {
+    let a = read_blast_file(a_path)?;
+    let b = read_blast_file(b_path)?;
+    let diff = diff_alignments(&a, &b);
+    if diff.unshared_monomers > 0 {
+        log::warn!(
+            "{} monomer(s) occur in only one of the alignments, are they of the same pair?",
+            diff.unshared_monomers
+        );
+    }
+
+    write_output(format!("{}.diff.rows.tsv", output_prefix), |writer| {
+        writeln!(
+            writer,
+            "#side\tmonomer\ta_partner\ta_type\tb_partner\tb_type"
+        )?;
+        for change in &diff.changes {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                change.side,
+                describe(&Some(change.monomer.clone())),
+                describe(&change.a_partner),
+                change.a_type,
+                describe(&change.b_partner),
+                change.b_type
+            )?;
+        }
+        Ok(())
+    })?;
+    write_output(format!("{}.diff.events.tsv", output_prefix), |writer| {
+        writeln!(writer, "#status\tkind\ta_event\tb_event")?;
+        for change in &diff.events {
+            let (status, kind, a_event, b_event) = match change {
+                EventChange::Appeared(event) => ("appeared", &event.kind, "-".to_string(), event.to_string()),
+                EventChange::Vanished(event) => ("vanished", &event.kind, event.to_string(), "-".to_string()),
+                EventChange::Shifted(a, b) => ("shifted", &a.kind, a.to_string(), b.to_string()),
+            };
+            writeln!(writer, "{}\t{}\t{}\t{}", status, kind, a_event, b_event)?;
+        }
+        Ok(())
+    })?;
+
+    // 标准输出为两张 TSV 表：两个比对的类型计数，以及一致性统计
+    println!("#alignment\tfile\tMTH\tMIS\tINS\tDEL");
+    for (name, path, count) in [("a", a_path, &diff.a_count), ("b", b_path, &diff.b_count)] {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            name, path, count.mth, count.mis, count.ins, count.del
+        );
+    }
+    println!();
+    println!("#statistic\tcount\ttotal\tfraction");
+    let count_status =
+        |status: fn(&EventChange) -> bool| diff.events.iter().filter(|e| status(e)).count();
+    let all_events = diff.unchanged_events + diff.events.len();
+    let statistics = [
+        (
+            "source_same_partner",
+            diff.shared_source - diff.changed_source,
+            diff.shared_source,
+        ),
+        (
+            "target_same_partner",
+            diff.shared_target - diff.changed_target,
+            diff.shared_target,
+        ),
+        ("shared_aligned_pairs", diff.shared_pairs, diff.union_pairs),
+        ("events_unchanged", diff.unchanged_events, all_events),
+        (
+            "events_shifted",
+            count_status(|e| matches!(e, EventChange::Shifted(..))),
+            all_events,
+        ),
+        (
+            "events_vanished",
+            count_status(|e| matches!(e, EventChange::Vanished(..))),
+            all_events,
+        ),
+        (
+            "events_appeared",
+            count_status(|e| matches!(e, EventChange::Appeared(..))),
+            all_events,
+        ),
+    ];
+    for (name, count, total) in statistics {
+        println!(
+            "{}\t{}\t{}\t{:.4}",
+            name,
+            count,
+            total,
+            fraction(count, total)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::bed;
+    use crate::horscan::align;
+    use crate::io::Mode;
+
+    #[test]
+    fn test_diff_alignments() -> Result<(), HorScanError> {
+        let source = bed("s", "ABCDEFGH");
+        let target = bed("t", "ABDEXFGH");
+        let a = align(&source, &target, &Mode::from_params(&[10, 4, 2]))?.rows;
+        let same = diff_alignments(&a, &a);
+        assert!(same.changes.is_empty() && same.events.is_empty());
+        assert_eq!(same.shared_pairs, same.union_pairs);
+        assert_eq!(same.unshared_monomers, 0);
+
+        // 以三个错配 C-D D-E E-X 代替 INS C 与 DEL X
+        let b: Vec<MonAlignmentRow> = source
+            .iter()
+            .zip(&target)
+            .map(|(s, t)| MonAlignmentRow {
+                source: s.sample.clone(),
+                source_start: s.start,
+                source_end: s.end,
+                source_mon: s.mon.clone(),
+                target: t.sample.clone(),
+                target_start: t.start,
+                target_end: t.end,
+                target_mon: t.mon.clone(),
+                align_type: if s.mon == t.mon { "MTH" } else { "MIS" }.to_string(),
+            })
+            .collect();
+        let diff = diff_alignments(&a, &b);
+        // source 的 C D E 与 target 的 D E X 都换了对应单体
+        assert_eq!((diff.changed_source, diff.changed_target), (3, 3));
+        assert_eq!(diff.changes[3].side, "target");
+        assert_eq!(diff.changes[3].monomer.3, "D");
+        let statuses: Vec<&str> = diff
+            .events
+            .iter()
+            .map(|e| match e {
+                EventChange::Appeared(_) => "appeared",
+                EventChange::Vanished(_) => "vanished",
+                EventChange::Shifted(..) => "shifted",
+            })
+            .collect();
+        assert_eq!(statuses, ["vanished", "vanished", "appeared"]);
+        assert_eq!((diff.shared_pairs, diff.union_pairs), (5, 10));
+        assert_eq!(diff.unshared_monomers, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+use crate::io::{
+    parse_field, read_blast_file, read_data_lines, split_columns, write_output, HorScanError,
+};
+use crate::sv::{call_events, SvEvent};
+use std::io::Write;
+
+/// 真值表中的一个事件，格式与 simulate 写出的 `.truth.tsv` 相同
+#[derive(Clone, Debug)]
+pub struct TruthEvent {
+    pub id: String,
+    pub kind: String,
+    /// INS（source 缺少单体）/DEL（target 缺少单体）/MIS/INV
+    pub sv_type: String,
+    pub source: String,
+    pub source_start: i32,
+    pub source_end: i32,
+    pub target: String,
+    pub target_start: i32,
+    pub target_end: i32,
+    pub source_monomers: usize,
+    pub target_monomers: usize,
+}
+
+/// 事件匹配的阈值
+#[derive(Clone, Debug)]
+pub struct EvalOptions {
+    /// 断点（起点与终点）最大距离 (bp)
+    pub max_distance: i32,
+    /// 单体数相似度 min/max 的下限
+    pub min_size_similarity: f64,
+}
+
+/// 读取真值表：至少 11 列，id、kind、sv_type、source 区间、target 区间和两侧单体数
+pub fn read_truth(path: &str) -> Result<Vec<TruthEvent>, HorScanError> {
+    let mut events = Vec::new();
+    for (line, text) in read_data_lines(path)? {
+        let parts = split_columns(&text, 11, path, line)?;
+        let number = |column: usize, name: &str| parse_field::<i32>(parts[column], name, path, line, column + 1);
+        let count = |column: usize, name: &str| parse_field::<usize>(parts[column], name, path, line, column + 1);
+        events.push(TruthEvent {
+            id: parts[0].to_string(),
+            kind: parts[1].to_string(),
+            sv_type: parts[2].to_string(),
+            source: parts[3].to_string(),
+            source_start: number(4, "source start")?,
+            source_end: number(5, "source end")?,
+            target: parts[6].to_string(),
+            target_start: number(7, "target start")?,
+            target_end: number(8, "target end")?,
+            source_monomers: count(9, "source monomers")?,
+            target_monomers: count(10, "target monomers")?,
+        });
+    }
+    Ok(events)
+}
+
+/// 读取事件表（hor 写出的 `.hor.sv.tsv` 格式）：kind、source 区间、target 区间、单体数
+pub fn read_events(path: &str) -> Result<Vec<SvEvent>, HorScanError> {
+    let mut events = Vec::new();
+    for (line, text) in read_data_lines(path)? {
+        let parts = split_columns(&text, 8, path, line)?;
+        let number = |column: usize, name: &str| parse_field::<i32>(parts[column], name, path, line, column + 1);
+        events.push(SvEvent {
+            kind: parts[0].to_string(),
+            source: parts[1].to_string(),
+            source_start: number(2, "source start")?,
+            source_end: number(3, "source end")?,
+            target: parts[4].to_string(),
+            target_start: number(5, "target start")?,
+            target_end: number(6, "target end")?,
+            monomers: parse_field(parts[7], "monomers", path, line, 8)?,
+        });
+    }
+    Ok(events)
+}
+
+/// 按哪一侧为空位判断事件类型（与 README 的定义一致）：source 为空是 INS，target 为空是 DEL
+pub fn event_type(event: &SvEvent) -> &'static str {
+    if event.source_start == event.source_end {
+        "INS"
+    } else if event.target_start == event.target_end {
+        "DEL"
+    } else {
+        "MIS"
+    }
+}
+
+/// 同一样本上两个区间 (样本, 起点, 终点) 的断点距离，取起点与终点距离的较大者；样本不同时为 None
+fn breakpoint_distance(a: (&str, i32, i32), b: (&str, i32, i32)) -> Option<i32> {
+    (a.0 == b.0).then(|| (a.1 - b.1).abs().max((a.2 - b.2).abs()))
+}
+
+/// 一对真值与调用事件的断点距离和大小相似度；类型不兼容（倒位可与错配匹配）或样本不同时为 None
+fn compare(truth: &TruthEvent, call: &SvEvent) -> Option<(i32, f64)> {
+    let call_type = event_type(call);
+    let compatible = truth.sv_type == call_type || (truth.sv_type == "INV" && call_type == "MIS");
+    if !compatible {
+        return None;
+    }
+    let source = breakpoint_distance(
+        (&truth.source, truth.source_start, truth.source_end),
+        (&call.source, call.source_start, call.source_end),
+    );
+    let target = breakpoint_distance(
+        (&truth.target, truth.target_start, truth.target_end),
+        (&call.target, call.target_start, call.target_end),
+    );
+    // INS 只有 target 一侧有单体，DEL 只有 source 一侧；MIS 与 INV 两侧都有单体，
+    // 串联重复中比对可能把事件放在相差整数个单元的拷贝上，任一侧的断点吻合即可
+    let (distance, size) = match call_type {
+        "INS" => (target?, truth.target_monomers),
+        "DEL" => (source?, truth.source_monomers),
+        _ => (source.into_iter().chain(target).min()?, truth.source_monomers),
+    };
+    let similarity = size.min(call.monomers) as f64 / size.max(call.monomers).max(1) as f64;
+    Some((distance, similarity))
+}
+
+/// 一一匹配：满足阈值的候选对按断点距离从小到大（相同时大小相似度高者优先）贪心选取；
+/// 返回每个真值事件匹配到的调用下标，以及每个调用匹配到的真值下标
+pub fn match_events(
+    truth: &[TruthEvent],
+    calls: &[SvEvent],
+    options: &EvalOptions,
+) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+    let mut candidates = Vec::new();
+    for (t, truth_event) in truth.iter().enumerate() {
+        for (c, call) in calls.iter().enumerate() {
+            if let Some((distance, similarity)) = compare(truth_event, call) {
+                if distance <= options.max_distance && similarity >= options.min_size_similarity {
+                    candidates.push((distance, similarity, t, c));
+                }
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)).then((a.2, a.3).cmp(&(b.2, b.3))));
+    let mut truth_match = vec![None; truth.len()];
+    let mut call_match = vec![None; calls.len()];
+    for (_, _, t, c) in candidates {
+        if truth_match[t].is_none() && call_match[c].is_none() {
+            truth_match[t] = Some(c);
+            call_match[c] = Some(t);
+        }
+    }
+    (truth_match, call_match)
+}
+
+fn ratio(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+/// eval 命令：将调用的事件（比对文件或事件表）与真值表匹配，写出 `<prefix>.eval.truth.tsv`
+/// 和 `<prefix>.eval.calls.tsv`，并在标准输出按类型打印 precision/recall/F1
+pub fn run_eval(
+    truth_path: &str,
+    alignment_path: Option<&str>,
+    calls_path: Option<&str>,
+    output_prefix: &str,
+    options: &EvalOptions,
+) -> Result<(), HorScanError> {
+    let truth = read_truth(truth_path)?;
+    let calls = match (alignment_path, calls_path) {
+        (Some(path), _) => call_events(&read_blast_file(path)?),
+        (None, Some(path)) => read_events(path)?,
+        (None, None) => {
+            return Err(HorScanError::InvalidValue(
+                "eval needs --alignment or --calls".to_string(),
+            ))
+        }
+    };
+    let (truth_match, call_match) = match_events(&truth, &calls, options);
+
+    write_output(format!("{}.eval.truth.tsv", output_prefix), |writer| {
+        writeln!(writer, "#id\tkind\tsv_type\tsource\tsource_start\tsource_end\ttarget\ttarget_start\ttarget_end\tstatus\tcall")?;
+        for (event, matched) in truth.iter().zip(&truth_match) {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                event.id,
+                event.kind,
+                event.sv_type,
+                event.source,
+                event.source_start,
+                event.source_end,
+                event.target,
+                event.target_start,
+                event.target_end,
+                if matched.is_some() { "TP" } else { "FN" },
+                matched.map(|c| format!("call{}", c + 1)).unwrap_or_else(|| ".".to_string())
+            )?;
+        }
+        Ok(())
+    })?;
+    write_output(format!("{}.eval.calls.tsv", output_prefix), |writer| {
+        writeln!(writer, "#id\tsv_type\tsource\tsource_start\tsource_end\ttarget\ttarget_start\ttarget_end\tmonomers\tstatus\ttruth")?;
+        for (idx, (call, matched)) in calls.iter().zip(&call_match).enumerate() {
+            writeln!(
+                writer,
+                "call{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                idx + 1,
+                event_type(call),
+                call.source,
+                call.source_start,
+                call.source_end,
+                call.target,
+                call.target_start,
+                call.target_end,
+                call.monomers,
+                if matched.is_some() { "TP" } else { "FP" },
+                matched.map(|t| truth[t].id.clone()).unwrap_or_else(|| ".".to_string())
+            )?;
+        }
+        Ok(())
+    })?;
+
+    println!("#type\ttruth\tcalls\tTP\tFP\tFN\tprecision\trecall\tF1");
+    for sv_type in ["INS", "DEL", "MIS", "INV", "all"] {
+        let in_type = |t: &str| sv_type == "all" || t == sv_type;
+        let truth_total = truth.iter().filter(|e| in_type(&e.sv_type)).count();
+        let tp_truth = truth
+            .iter()
+            .zip(&truth_match)
+            .filter(|(e, m)| in_type(&e.sv_type) && m.is_some())
+            .count();
+        // 调用事件没有 INV 类型，按其匹配到的真值计入 INV
+        let call_type = |c: usize| match call_match[c] {
+            Some(t) if truth[t].sv_type == "INV" => "INV",
+            _ => event_type(&calls[c]),
+        };
+        let calls_total = (0..calls.len()).filter(|&c| in_type(call_type(c))).count();
+        let tp_calls = (0..calls.len())
+            .filter(|&c| in_type(call_type(c)) && call_match[c].is_some())
+            .count();
+        if truth_total == 0 && calls_total == 0 {
+            continue;
+        }
+        let precision = ratio(tp_calls, calls_total);
+        let recall = ratio(tp_truth, truth_total);
+        let f1 = if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
+        } else {
+            0.0
+        };
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{:.4}\t{:.4}",
+            sv_type,
+            truth_total,
+            calls_total,
+            tp_truth,
+            calls_total - tp_calls,
+            truth_total - tp_truth,
+            precision,
+            recall,
+            f1
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::horscan::align;
+    use crate::io::{read_bed_file, Mode};
+    use crate::simulate::{run_simulate, EventRates, SimulateOptions};
+    use crate::testutil::{call, truth, TestDir};
+
+    #[test]
+    fn test_match_events() {
+        let truth = vec![
+            truth("del", "DEL", (1000, 2367), (900, 900), 8),
+            truth("ins", "INS", (5000, 5000), (4000, 5367), 8),
+            truth("mis", "MIS", (7000, 7170), (6000, 6170), 1),
+            truth("shifted", "MIS", (9736, 9906), (8000, 8170), 1),
+        ];
+        let calls = vec![
+            // 类型按空位一侧判断，与标签无关
+            call("INS", (1171, 2538), (729, 729), 8),
+            call("DEL", (4829, 4829), (4171, 5367), 5),
+            call("MIS", (7000, 7170), (6000, 6170), 1),
+            // 错配只在 target 一侧吻合
+            call("MIS", (5000, 5170), (8000, 8170), 1),
+        ];
+        let options = EvalOptions {
+            max_distance: 500,
+            min_size_similarity: 0.7,
+        };
+        let (truth_match, call_match) = match_events(&truth, &calls, &options);
+        // 插入的大小相似度 5/8 不足 0.7
+        assert_eq!(truth_match, [Some(0), None, Some(2), Some(3)]);
+        assert_eq!(call_match, [Some(0), None, Some(2), Some(3)]);
+        let loose = EvalOptions {
+            max_distance: 500,
+            min_size_similarity: 0.5,
+        };
+        assert_eq!(match_events(&truth, &calls, &loose).0[1], Some(1));
+    }
+
+    #[test]
+    fn test_simulate_align_eval_round_trip() -> Result<(), HorScanError> {
+        let dir = TestDir::new("eval_round_trip");
+        let prefix = dir.path("sim").display().to_string();
+        let options = SimulateOptions {
+            unit: "ABCDEFGH".chars().map(|c| c.to_string()).collect(),
+            copies: 100,
+            rates: EventRates {
+                expansion: 0.02,
+                contraction: 0.02,
+                substitution: 0.002,
+                partial_indel: 0.01,
+                inversion: 0.005,
+            },
+            max_copies: 3,
+            monomer_length: 171,
+            source_name: "sim#source".to_string(),
+            target_name: "sim#target".to_string(),
+            seed: 7,
+        };
+        run_simulate(&options, &prefix)?;
+        let source = read_bed_file(format!("{}.source.bed", prefix))?;
+        let target = read_bed_file(format!("{}.target.bed", prefix))?;
+        let alignment = align(&source, &target, &Mode::from_params(&[10, 4, 2]))?;
+        let truth = read_truth(&format!("{}.truth.tsv", prefix))?;
+        let eval_options = EvalOptions {
+            max_distance: 500,
+            min_size_similarity: 0.7,
+        };
+        let (truth_match, _) = match_events(&truth, &call_events(&alignment.rows), &eval_options);
+        // 替换在 source 一侧相差两个单元，只能在 target 一侧吻合
+        let matched: Vec<&str> = truth
+            .iter()
+            .zip(&truth_match)
+            .filter(|(_, m)| m.is_some())
+            .map(|(event, _)| event.sv_type.as_str())
+            .collect();
+        assert!(matched.contains(&"MIS"));
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+use crate::horscan::{align, load_bed};
+use crate::io::{save_blast_file, write_output, HorScanError, Mode, MonRow};
+use crate::sv::call_events;
+use crate::validate::ValidationMode;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// 低于该周期性得分（相隔 p 个单体标签相同的比例）时认为没有 HOR 结构
+const MIN_PERIOD_SCORE: f64 = 0.3;
+/// 最多推断的 HOR 单元数
+const MAX_UNITS: usize = 4;
+/// 默认搜索的最长 HOR 单元（单体数）
+pub const DEFAULT_MAX_PERIOD: usize = 40;
+/// 判断起点相位时使用的开头单元拷贝数
+const PHASE_COPIES: usize = 3;
+
+/// 一个 HOR 单元：按顺序排列的单体标签
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HorUnit {
+    /// 从 1 开始的编号
+    pub id: usize,
+    pub monomers: Vec<String>,
+}
+
+/// 阵列中的一个 HOR 拷贝，index 为单体在排序后 BED 中的下标（左闭右开）
+#[derive(Clone, Debug)]
+pub struct HorCopy {
+    pub unit: usize,
+    pub start_index: usize,
+    pub end_index: usize,
+    /// 与单元完全一致时为 canonical，否则为 variant
+    pub canonical: bool,
+}
+
+/// 相隔 period 个单体标签相同的比例
+pub fn period_score(labels: &[&str], period: usize) -> f64 {
+    if period == 0 || period >= labels.len() {
+        return 0.0;
+    }
+    let matches = labels
+        .iter()
+        .zip(&labels[period..])
+        .filter(|(a, b)| a == b)
+        .count();
+    matches as f64 / (labels.len() - period) as f64
+}
+
+/// 推断主周期：得分不低于最高分 95% 的最小周期（避免选到周期的整数倍），返回 (周期, 得分)
+pub fn infer_period(labels: &[&str], max_period: usize) -> Option<(usize, f64)> {
+    let scores: Vec<(usize, f64)> = (1..=max_period.min(labels.len() / 2))
+        .map(|period| (period, period_score(labels, period)))
+        .collect();
+    let best = scores.iter().map(|s| s.1).fold(0.0, f64::max);
+    if best < MIN_PERIOD_SCORE {
+        return None;
+    }
+    scores.into_iter().find(|s| s.1 >= 0.95 * best)
+}
+
+/// 长度为 period 的窗口中出现次数最多的一个（次数相同取先出现的），至少出现两次
+fn most_common_window<'a>(labels: &[&'a str], period: usize) -> Option<Vec<&'a str>> {
+    let mut counts: HashMap<&[&str], (usize, usize)> = HashMap::new();
+    for (i, window) in labels.windows(period).enumerate() {
+        counts.entry(window).or_insert((0, i)).0 += 1;
+    }
+    counts
+        .into_iter()
+        .filter(|(_, (count, _))| *count >= 2)
+        .max_by(|a, b| a.1 .0.cmp(&b.1 .0).then(b.1 .1.cmp(&a.1 .1)))
+        .map(|(window, _)| window.to_vec())
+}
+
+/// 自身也是重复的窗口（如几种 HOR 混排时取到的公倍数周期）缩短为最短的重复单位
+fn primitive_unit<'a>(window: &[&'a str]) -> Vec<&'a str> {
+    let len = (1..window.len())
+        .filter(|&len| window.len().is_multiple_of(len))
+        .find(|&len| (len..window.len()).all(|i| window[i] == window[i % len]))
+        .unwrap_or(window.len());
+    window[..len].to_vec()
+}
+
+/// 从标签序列推断 HOR 单元：先推断主单元，再在未被已知单元完整覆盖的片段中继续寻找
+/// 由其他单体组成的单元
+pub fn infer_units(labels: &[&str], max_period: usize) -> Vec<HorUnit> {
+    let mut units: Vec<HorUnit> = Vec::new();
+    let mut remaining: Vec<&str> = labels.to_vec();
+    while units.len() < MAX_UNITS {
+        let Some((period, _)) = infer_period(&remaining, max_period) else {
+            break;
+        };
+        let Some(window) = most_common_window(&remaining, period) else {
+            break;
+        };
+        let window = primitive_unit(&window);
+        if is_known_variant(&window, &units) {
+            break;
+        }
+        units.push(HorUnit {
+            id: units.len() + 1,
+            monomers: window.iter().map(|s| s.to_string()).collect(),
+        });
+
+        // 只保留不属于任何 canonical 拷贝的单体，继续推断其余单元
+        let copies = segment_labels(labels, &units);
+        let mut rest = Vec::new();
+        for copy in copies.iter().filter(|copy| !copy.canonical) {
+            rest.extend_from_slice(&labels[copy.start_index..copy.end_index]);
+        }
+        if rest.len() == remaining.len() || rest.len() < 4 {
+            break;
+        }
+        remaining = rest;
+    }
+    units
+}
+
+/// 与已知单元共用过半单体的窗口只是已知单元的变体（包括循环旋转）
+fn is_known_variant<S: AsRef<str>>(window: &[S], units: &[HorUnit]) -> bool {
+    let known = window
+        .iter()
+        .filter(|label| {
+            units
+                .iter()
+                .any(|unit| unit.monomers.iter().any(|m| m == label.as_ref()))
+        })
+        .count();
+    2 * known > window.len()
+}
+
+/// 两条阵列各自推断单元后合并：以 source 的单元为准，target 中不是已知单元变体的单元
+/// 依次追加编号；不把两侧标签拼成一条序列，避免交界处形成虚假的周期窗口
+pub fn infer_shared_units(source: &[&str], target: &[&str], max_period: usize) -> Vec<HorUnit> {
+    let mut units = infer_units(source, max_period);
+    for unit in infer_units(target, max_period) {
+        if units.len() < MAX_UNITS && !is_known_variant(&unit.monomers, &units) {
+            units.push(HorUnit {
+                id: units.len() + 1,
+                monomers: unit.monomers,
+            });
+        }
+    }
+    units
+}
+
+/// 按已知单元切分标签序列：从左到右优先匹配最长的单元得到 canonical 拷贝，
+/// 其余片段按相邻 canonical 拷贝所属单元的长度切成 variant 拷贝
+pub fn segment_labels(labels: &[&str], units: &[HorUnit]) -> Vec<HorCopy> {
+    let mut order: Vec<&HorUnit> = units.iter().collect();
+    order.sort_by_key(|unit| std::cmp::Reverse(unit.monomers.len()));
+
+    let mut copies: Vec<HorCopy> = Vec::new();
+    let mut variant_start: Option<usize> = None;
+    let mut i = 0;
+    while i < labels.len() {
+        let hit = order.iter().find(|unit| {
+            let len = unit.monomers.len();
+            i + len <= labels.len() && unit.monomers.iter().zip(&labels[i..i + len]).all(|(a, b)| a == b)
+        });
+        match hit {
+            Some(unit) => {
+                if let Some(start) = variant_start.take() {
+                    copies.push(HorCopy {
+                        unit: 0,
+                        start_index: start,
+                        end_index: i,
+                        canonical: false,
+                    });
+                }
+                let len = unit.monomers.len();
+                copies.push(HorCopy {
+                    unit: unit.id,
+                    start_index: i,
+                    end_index: i + len,
+                    canonical: true,
+                });
+                i += len;
+            }
+            None => {
+                variant_start.get_or_insert(i);
+                i += 1;
+            }
+        }
+    }
+    if let Some(start) = variant_start {
+        copies.push(HorCopy {
+            unit: 0,
+            start_index: start,
+            end_index: labels.len(),
+            canonical: false,
+        });
+    }
+
+    // variant 片段归属于前一个（没有时为后一个）canonical 拷贝的单元，并按单元长度切分
+    let mut result: Vec<HorCopy> = Vec::with_capacity(copies.len());
+    for idx in 0..copies.len() {
+        let copy = &copies[idx];
+        if copy.canonical {
+            result.push(copy.clone());
+            continue;
+        }
+        let unit = copies[..idx]
+            .iter()
+            .rev()
+            .chain(copies[idx + 1..].iter())
+            .find(|c| c.canonical)
+            .map(|c| c.unit)
+            .unwrap_or(0);
+        let period = units
+            .iter()
+            .find(|u| u.id == unit)
+            .map(|u| u.monomers.len())
+            .unwrap_or(copy.end_index - copy.start_index)
+            .max(1);
+        let len = copy.end_index - copy.start_index;
+        let pieces = ((len as f64 / period as f64).round() as usize).max(1);
+        for piece in 0..pieces {
+            let start = copy.start_index + piece * period;
+            let end = if piece + 1 == pieces {
+                copy.end_index
+            } else {
+                start + period
+            };
+            result.push(HorCopy {
+                unit,
+                start_index: start,
+                end_index: end,
+                canonical: false,
+            });
+        }
+    }
+    result
+}
+
+/// HOR 拷贝的标签：canonical 为 `H<单元>`，variant 附加其单体标签，相同的 variant 标签相同
+fn copy_label(copy: &HorCopy, labels: &[&str]) -> String {
+    if copy.canonical {
+        format!("H{}", copy.unit)
+    } else {
+        format!(
+            "H{}/{}",
+            copy.unit,
+            labels[copy.start_index..copy.end_index].join(".")
+        )
+    }
+}
+
+/// 两条阵列起点在共同的主 HOR 单元中的相位
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Phase {
+    /// source 一侧推断出的单元，target 的单元是它的一个循环旋转
+    pub unit: Vec<String>,
+    /// 第一个单体在单元中的下标
+    pub source_phase: usize,
+    pub target_phase: usize,
+    /// target 相位减 source 相位（模单元长度）
+    pub offset: usize,
+}
+
+impl Phase {
+    /// 写入比对文件开头的注释行
+    pub fn header(&self) -> String {
+        format!(
+            "#phase\tperiod={}\tunit={}\tsource_phase={}\ttarget_phase={}\toffset={}",
+            self.unit.len(),
+            self.unit.join(","),
+            self.source_phase,
+            self.target_phase,
+            self.offset
+        )
+    }
+}
+
+/// 阵列开头与单元各个循环旋转最吻合的一个，吻合的单体不足一半时返回 None
+fn array_phase(labels: &[&str], unit: &[String]) -> Option<usize> {
+    let period = unit.len();
+    let head = &labels[..labels.len().min(PHASE_COPIES * period)];
+    let (best, matches) = (0..period)
+        .map(|phase| {
+            let matches = head
+                .iter()
+                .enumerate()
+                .filter(|(i, label)| unit[(i + phase) % period] == **label)
+                .count();
+            (phase, matches)
+        })
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))?;
+    (2 * matches >= head.len()).then_some(best)
+}
+
+/// 两侧各自推断主 HOR 单元（按坐标排序），单元互为循环旋转时视为共同单元，
+/// 并以 source 的单元为准给出两侧起点的相位；任一侧没有单元或单元不同时返回 None
+pub fn detect_phase(source: &[MonRow], target: &[MonRow], max_period: usize) -> Option<Phase> {
+    let source_labels: Vec<&str> = source.iter().map(|row| row.mon.as_str()).collect();
+    let target_labels: Vec<&str> = target.iter().map(|row| row.mon.as_str()).collect();
+    let main_unit = |labels: &[&str]| Some(infer_units(labels, max_period).first()?.monomers.clone());
+    let unit = main_unit(&source_labels)?;
+    let target_unit = main_unit(&target_labels)?;
+    let period = unit.len();
+    let rotated = |r: usize| (0..period).all(|i| target_unit[i] == unit[(i + r) % period]);
+    if target_unit.len() != period || !(0..period).any(rotated) {
+        log::debug!(
+            "source unit {} and target unit {} differ",
+            unit.join(","),
+            target_unit.join(",")
+        );
+        return None;
+    }
+    let source_phase = array_phase(&source_labels, &unit)?;
+    let target_phase = array_phase(&target_labels, &unit)?;
+    Some(Phase {
+        offset: (target_phase + period - source_phase) % period,
+        unit,
+        source_phase,
+        target_phase,
+    })
+}
+
+/// 两层视图中的上层：每个 HOR 拷贝作为一个“单体”，可直接用于比对和 SV 调用
+pub fn hor_rows(bed: &[MonRow], copies: &[HorCopy]) -> Vec<MonRow> {
+    let labels: Vec<&str> = bed.iter().map(|row| row.mon.as_str()).collect();
+    copies
+        .iter()
+        .map(|copy| MonRow {
+            sample: bed[copy.start_index].sample.clone(),
+            start: bed[copy.start_index].start,
+            end: bed[copy.end_index - 1].end,
+            mon: copy_label(copy, &labels),
+        })
+        .collect()
+}
+
+fn save_hor_bed(path: String, bed: &[MonRow], copies: &[HorCopy]) -> Result<(), HorScanError> {
+    let rows = hor_rows(bed, copies);
+    write_output(path, |writer| {
+        writeln!(writer, "#chrom\tstart\tend\tlabel\tunit\tstatus\tmonomers")?;
+        for (row, copy) in rows.iter().zip(copies) {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                row.sample,
+                row.start,
+                row.end,
+                row.mon,
+                copy.unit,
+                if copy.canonical { "canonical" } else { "variant" },
+                copy.end_index - copy.start_index
+            )?;
+        }
+        Ok(())
+    })
+}
+
+fn sorted_bed(path: &str, validation: ValidationMode) -> Result<Vec<MonRow>, HorScanError> {
+    let mut bed = load_bed(path, validation)?;
+    bed.sort_by_key(|row| row.start);
+    Ok(bed)
+}
+
+/// hor 命令：推断 HOR 单元并切分阵列；给出 target 时两条阵列共用一组单元，
+/// 并在 HOR 层面比对，写出 HOR 级别的比对和 SV 事件
+pub fn run_hor(
+    source_path: &str,
+    target_path: Option<&str>,
+    output_prefix: &str,
+    max_period: usize,
+    mode: &Mode,
+    validation: ValidationMode,
+) -> Result<(), HorScanError> {
+    let source = sorted_bed(source_path, validation)?;
+    let target = match target_path {
+        Some(path) => Some(sorted_bed(path, validation)?),
+        None => None,
+    };
+    let source_labels: Vec<&str> = source.iter().map(|row| row.mon.as_str()).collect();
+    let target_labels: Vec<&str> = target
+        .iter()
+        .flatten()
+        .map(|row| row.mon.as_str())
+        .collect();
+    let units = infer_shared_units(&source_labels, &target_labels, max_period);
+    if units.is_empty() {
+        return Err(HorScanError::InvalidValue(format!(
+            "no periodic HOR structure found with periods up to {}",
+            max_period
+        )));
+    }
+
+    let segment = |bed: &[MonRow]| {
+        let labels: Vec<&str> = bed.iter().map(|row| row.mon.as_str()).collect();
+        segment_labels(&labels, &units)
+    };
+    let source_copies = segment(&source);
+    let target_copies = target.as_deref().map(segment);
+
+    write_output(format!("{}.units.tsv", output_prefix), |writer| {
+        writeln!(writer, "#unit\tperiod\tcanonical\tvariant\tmonomers")?;
+        for unit in &units {
+            let count = |canonical: bool| {
+                source_copies
+                    .iter()
+                    .chain(target_copies.iter().flatten())
+                    .filter(|c| c.unit == unit.id && c.canonical == canonical)
+                    .count()
+            };
+            writeln!(
+                writer,
+                "H{}\t{}\t{}\t{}\t{}",
+                unit.id,
+                unit.monomers.len(),
+                count(true),
+                count(false),
+                unit.monomers.join(",")
+            )?;
+            log::info!(
+                "HOR unit H{}: {} monomers, {} canonical and {} variant copies",
+                unit.id,
+                unit.monomers.len(),
+                count(true),
+                count(false)
+            );
+        }
+        Ok(())
+    })?;
+    save_hor_bed(format!("{}.hor.bed", output_prefix), &source, &source_copies)?;
+
+    if let (Some(target), Some(target_copies)) = (&target, &target_copies) {
+        save_hor_bed(
+            format!("{}.target.hor.bed", output_prefix),
+            target,
+            target_copies,
+        )?;
+        let alignment = align(
+            &hor_rows(&source, &source_copies),
+            &hor_rows(target, target_copies),
+            mode,
+        )?;
+        log::info!("HOR-level alignment: {:?}", alignment.count);
+        save_blast_file(&alignment.rows, format!("{}.hor.alignment", output_prefix))?;
+        let events = call_events(&alignment.rows);
+        write_output(format!("{}.hor.sv.tsv", output_prefix), |writer| {
+            writeln!(
+                writer,
+                "#kind\tsource\tsource_start\tsource_end\ttarget\ttarget_start\ttarget_end\tcopies"
+            )?;
+            for event in &events {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    event.kind,
+                    event.source,
+                    event.source_start,
+                    event.source_end,
+                    event.target,
+                    event.target_start,
+                    event.target_end,
+                    event.monomers
+                )?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::bed_words;
+
+    fn split(labels: &str) -> Vec<&str> {
+        labels.split(' ').collect()
+    }
+
+    #[test]
+    fn test_infer_period_and_unit() {
+        let labels = split("A B C D A B C D A B C D A B X D A B C D");
+        assert_eq!(infer_period(&labels, 10).map(|p| p.0), Some(4));
+        let units = infer_units(&labels, 10);
+        assert_eq!(units[0].monomers, ["A", "B", "C", "D"]);
+        assert!(infer_period(&split("A B C D E F G H I J"), 5).is_none());
+        assert_eq!(primitive_unit(&split("A B C A B C")), ["A", "B", "C"]);
+
+        // 两侧分别推断：target 的旋转单元归入 source 的单元，只在 target 出现的单元另行编号
+        let source = ["A", "B", "C", "D"].repeat(5);
+        let units = infer_shared_units(&source, &["C", "D", "A", "B"].repeat(5), 10);
+        assert_eq!(units.len(), 1);
+        let units = infer_shared_units(&source, &["E", "F", "G"].repeat(6), 10);
+        assert_eq!(units[1].monomers, ["E", "F", "G"]);
+        assert_eq!(units[1].id, 2);
+    }
+
+    #[test]
+    fn test_detect_phase() {
+        let bed = |labels: &str| bed_words("chrX", labels);
+        let source = bed("A B C D E F A B C D E F A B C D E F");
+        // target 从单元第 5 个单体开始，单独推断出的单元是 E F A B C D
+        let target = bed("E F A B C D E F A B C D E F A B");
+        let phase = detect_phase(&source, &target, 10).unwrap();
+        assert_eq!(phase.unit, ["A", "B", "C", "D", "E", "F"]);
+        assert_eq!((phase.source_phase, phase.target_phase, phase.offset), (0, 4, 4));
+        assert!(phase.header().starts_with("#phase\tperiod=6\t"));
+
+        // 两侧起点都不在单元开头：相位以 source 一侧推断出的单元为准
+        let source = bed("C D E F A B C D E F A B C D E F A B");
+        let phase = detect_phase(&source, &target, 10).unwrap();
+        assert_eq!(phase.unit, ["C", "D", "E", "F", "A", "B"]);
+        assert_eq!((phase.source_phase, phase.target_phase, phase.offset), (0, 2, 2));
+
+        // 两侧单元不同
+        let other = bed("A B C X Y A B C X Y A B C X Y A B C");
+        assert!(detect_phase(&source, &other, 10).is_none());
+    }
+
+    #[test]
+    fn test_segment_labels() {
+        let labels = split("A B C D A B C D A B D A B C D C D");
+        let units = vec![HorUnit {
+            id: 1,
+            monomers: vec!["A".into(), "B".into(), "C".into(), "D".into()],
+        }];
+        let copies = segment_labels(&labels, &units);
+        let spans: Vec<(usize, usize, bool)> = copies
+            .iter()
+            .map(|c| (c.start_index, c.end_index, c.canonical))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                (0, 4, true),
+                (4, 8, true),
+                (8, 11, false),
+                (11, 15, true),
+                (15, 17, false)
+            ]
+        );
+        assert!(copies.iter().all(|c| c.unit == 1));
+        assert_eq!(copy_label(&copies[2], &labels), "H1/A.B.D");
+    }
+}
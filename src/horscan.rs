@@ -1,13 +1,235 @@
-use crate::io::{read_bed_file, HorScanError, Mode, MonRow};
-use crate::optimize::optimize_blast_file;
-use std::fs::File;
-use std::io::Write;
-// use std::io::{BufWriter, Write};
+use crate::checkpoint::{intern_labels, TempStore};
+use crate::hor::{detect_phase, Phase, DEFAULT_MAX_PERIOD};
+use crate::io::{read_bed_records, save_blast_file_with_header, HorScanError, Mode, MonAlignmentRow, MonRow};
+use crate::labels::apply_label_map;
+use crate::logging::{Progress, StageRecord, StageTimer};
+use crate::matrix::{MatrixBackend, ScoreMatrix, TILE};
+use crate::npy::save_score_matrix_npy;
+use crate::optimize::{check_monomer_order, compact_alignment, optimize_alignment, TypeCount};
+use crate::region::{subset_region, Region};
+use crate::significance::{
+    check_permutation_input, permutation_test, save_significance, PermutationOptions,
+};
+use crate::summary::{save_summary, Summary};
+use crate::validate::{report_issues, validate_bed, ValidationMode};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// 单次比对的可选设置
+#[derive(Clone, Debug)]
+pub struct RunOptions {
+    /// 仅比对 source 中与该区域重叠的单体
+    pub source_region: Option<Region>,
+    /// 仅比对 target 中与该区域重叠的单体
+    pub target_region: Option<Region>,
+    /// 读取 BED 文件后的校验模式
+    pub validation: ValidationMode,
+    /// 是否在回溯后运行优化
+    pub optimize: bool,
+    /// 得分矩阵的存储方式
+    pub matrix_backend: MatrixBackend,
+    /// 将得分矩阵写为 `.npy` 的路径
+    pub dump_matrix: Option<String>,
+    /// 写出 `.npy` 时每一维的最大尺寸，超过时降采样
+    pub dump_max_size: Option<usize>,
+    /// 中间文件（得分矩阵检查点、优化前比对）的路径前缀，用于中断后续跑
+    pub temp_prefix: Option<String>,
+    /// 读取 BED 后应用到两侧单体的标签映射文件（仅命令行入口使用）
+    pub label_map: Option<String>,
+    /// 比对前检测两侧起点的 HOR 相位，并允许开头至多一个单元长度的空位不计分
+    pub phase: bool,
+    /// 开头不计分的空位数；给出时不再检测相位，置换检验用它沿用观测比对的设置
+    pub free_gaps: Option<usize>,
+    /// 比对后打乱 target 标签重新比对，估计得分的显著性（仅命令行入口使用）
+    pub permutation: Option<PermutationOptions>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            source_region: None,
+            target_region: None,
+            validation: ValidationMode::default(),
+            optimize: true,
+            matrix_backend: MatrixBackend::default(),
+            dump_matrix: None,
+            dump_max_size: None,
+            temp_prefix: None,
+            label_map: None,
+            phase: false,
+            free_gaps: None,
+            permutation: None,
+        }
+    }
+}
+
+/// 一次比对的结果
+#[derive(Clone, Debug)]
+pub struct Alignment {
+    /// 优化后的比对行，按 source/target 坐标顺序排列
+    pub rows: Vec<MonAlignmentRow>,
+    /// 动态规划回溯得到的原始比对类型统计
+    pub default_count: TypeCount,
+    /// 原始比对在 Mode 下的总分
+    pub default_score: i32,
+    /// 优化后的比对类型统计
+    pub count: TypeCount,
+    /// 优化后比对行在 Mode 下的总分
+    pub score: i32,
+    /// 各阶段 (fill/traceback/optimize) 的耗时与内存
+    pub stages: Vec<StageRecord>,
+    /// 启用相位检测且检测成功时的起点相位
+    pub phase: Option<Phase>,
+}
+
+/// 单个比对行在 Mode 下的得分：MTH/MIS 计匹配/错配分，INS/DEL 计 gap 分，其余类型不计分
+pub fn row_score(row: &MonAlignmentRow, mode: &Mode) -> i32 {
+    match row.align_type.as_str() {
+        "MTH" => mode.match_score,
+        "MIS" => mode.mismatch_score,
+        "INS" | "DEL" => mode.gap_score,
+        _ => 0,
+    }
+}
+
+/// 按 Mode 为比对行打分，即各行得分之和
+pub fn alignment_score(rows: &[MonAlignmentRow], mode: &Mode) -> i32 {
+    rows.iter().map(|row| row_score(row, mode)).sum()
+}
+
+/// 在内存中比对两组单体并优化比对结果，不读写任何文件
+pub fn align(source: &[MonRow], target: &[MonRow], mode: &Mode) -> Result<Alignment, HorScanError> {
+    align_with_options(source, target, mode, &RunOptions::default())
+}
+
+/// 与 [`align`] 相同，并应用区域限制等可选设置
+pub fn align_with_options(
+    source: &[MonRow],
+    target: &[MonRow],
+    mode: &Mode,
+    options: &RunOptions,
+) -> Result<Alignment, HorScanError> {
+    let mut source_bed = source.to_vec();
+    let mut target_bed = target.to_vec();
+    if let Some(region) = &options.source_region {
+        source_bed = subset_region(source_bed, region)?;
+    }
+    if let Some(region) = &options.target_region {
+        target_bed = subset_region(target_bed, region)?;
+    }
+    let mut phase = None;
+    let mut free_gaps = options.free_gaps.unwrap_or(0);
+    if options.phase && options.free_gaps.is_none() {
+        source_bed.sort_by_key(|row| row.start);
+        target_bed.sort_by_key(|row| row.start);
+        phase = detect_phase(&source_bed, &target_bed, DEFAULT_MAX_PERIOD);
+        match &phase {
+            Some(phase) => {
+                log::info!("{}", phase.header().trim_start_matches('#'));
+                free_gaps = phase.unit.len() - 1;
+            }
+            None => log::warn!("no common HOR unit found, aligning without phase detection"),
+        }
+    }
+    prepare_bed(&mut source_bed, "source")?;
+    prepare_bed(&mut target_bed, "target")?;
+
+    let store = match &options.temp_prefix {
+        Some(prefix) => Some(TempStore::open(
+            prefix,
+            &source_bed[1..],
+            &target_bed[1..],
+            mode,
+            free_gaps,
+        )?),
+        None => None,
+    };
+
+    let mut stages = Vec::new();
+    // 保存原始比对后得分矩阵检查点已删除，需要输出矩阵时只能重新填充
+    let resumed = match &store {
+        Some(_) if options.dump_matrix.is_some() => {
+            log::info!("--dump-matrix needs the score matrix, refilling instead of using the saved alignment");
+            None
+        }
+        Some(store) => store.load_raw_alignment()?,
+        None => None,
+    };
+    let mut rows = match resumed {
+        Some(rows) => {
+            log::info!("using the saved pre-optimisation alignment, skipping fill and traceback");
+            rows
+        }
+        None => {
+            // 调用HORSCAN的动态规划算法
+            let timer = StageTimer::start("fill");
+            let score = horscan_global_linear_score_alignment_low(
+                &source_bed,
+                &target_bed,
+                mode,
+                options,
+                free_gaps,
+                store.as_ref(),
+            )?;
+            stages.push(timer.finish());
+            if let Some(path) = &options.dump_matrix {
+                let timer = StageTimer::start("dump");
+                save_score_matrix_npy(&score, path, options.dump_max_size)?;
+                stages.push(timer.finish());
+            }
+            let timer = StageTimer::start("traceback");
+            let alignment_path = find_global_alignment_all_path_low(&score)?;
+            drop(score);
+            let rows = path_to_rows(&source_bed, &target_bed, &alignment_path)?;
+            if let Some(store) = &store {
+                store.save_raw_alignment(&rows)?;
+            }
+            stages.push(timer.finish());
+            rows
+        }
+    };
+    let default_count = TypeCount::from_rows(&rows);
+    let default_score = alignment_score(&rows, mode);
+
+    // optimize alignment path
+    if options.optimize {
+        let timer = StageTimer::start("optimize");
+        let report = optimize_alignment(&mut rows, mode)?;
+        log::debug!(
+            "optimize: score {} -> {} ({} moves, {} of them block moves, in {} passes)",
+            report.score_before,
+            report.score_after,
+            report.moves,
+            report.block_moves,
+            report.passes
+        );
+        stages.push(timer.finish());
+    }
+    let removed = compact_alignment(&mut rows);
+    if removed > 0 {
+        log::debug!("removed {} empty rows after optimisation", removed);
+    }
+    check_monomer_order(&rows, &source_bed[1..], &target_bed[1..])?;
+    let count = TypeCount::from_rows(&rows);
+    let score = alignment_score(&rows, mode);
+    Ok(Alignment {
+        rows,
+        default_count,
+        default_score,
+        count,
+        score,
+        stages,
+        phase,
+    })
+}
+
+/// 命令行入口：读取 BED 文件，比对后写出 `<output_prefix>.alignment`
 pub fn horscan_main(
     source_bed_path: String,
     target_bed_path: String,
     output_prefix: String,
     mode: Mode,
+    options: RunOptions,
 ) -> Result<(), HorScanError> {
     // 加载hor文件
     let path = output_prefix.clone() + ".alignment";
@@ -20,163 +242,279 @@ pub fn horscan_main(
     //     Err(_) => {}
     // }
 
-    let mut source_bed = read_bed_file(source_bed_path.as_str())?;
-    let mut target_bed = read_bed_file(target_bed_path.as_str())?;
-    source_bed.sort_by_key(|row| row.start);
-    target_bed.sort_by_key(|row| row.start);
-    let source_sample = source_bed[0].sample.clone();
-    let target_sample = target_bed[0].sample.clone();
-    print!(
-        "source sample: {} Monomer Length: {}\n",
-        source_sample,
+    let started = Instant::now();
+    let timer = StageTimer::start("parse");
+    let mut source_bed = load_bed(&source_bed_path, options.validation)?;
+    let mut target_bed = load_bed(&target_bed_path, options.validation)?;
+    if let Some(path) = &options.label_map {
+        apply_label_map(path, &mut [&mut source_bed, &mut target_bed])?;
+    }
+    let mut stages = vec![timer.finish()];
+    if options.permutation.is_some() {
+        check_permutation_input(&source_bed_path, &target_bed_path, &source_bed, &target_bed)?;
+    }
+    log::info!(
+        "source sample: {} Monomer Length: {}",
+        source_bed[0].sample,
         source_bed.len()
     );
-    print!(
-        "target sample: {} Monomer Length: {}\n",
-        target_sample,
+    log::info!(
+        "target sample: {} Monomer Length: {}",
+        target_bed[0].sample,
         target_bed.len()
     );
-    source_bed.insert(
-        0,
-        MonRow {
-            sample: source_sample.clone(),
-            start: 0,
-            end: 0,
-            mon: "-".to_string(),
-        },
+
+    log::debug!("memory update linear score");
+    let alignment = align_with_options(&source_bed, &target_bed, &mode, &options)?;
+    log::info!(
+        "Default Alignment: {:?} score {}",
+        alignment.default_count,
+        alignment.default_score
+    );
+    log::info!(
+        "Optimize Alignment: {:?} score {}",
+        alignment.count,
+        alignment.score
+    ); // 打印optimize 后的结果
+    log::info!("Alignment score: {}", alignment.score);
+
+    // 保存文件
+    let timer = StageTimer::start("write");
+    let header: Vec<String> = alignment.phase.iter().map(Phase::header).collect();
+    save_blast_file_with_header(&alignment.rows, &header, path)?;
+    stages.extend(alignment.stages.iter().cloned());
+    stages.push(timer.finish());
+
+    if let Some(permutation) = &options.permutation {
+        let timer = StageTimer::start("permutations");
+        let significance = permutation_test(
+            &source_bed,
+            &target_bed,
+            &mode,
+            &options,
+            &alignment,
+            permutation,
+        )?;
+        log::info!(
+            "score {} vs {} permutations ({}): mean {:.1}, z {:.2}, empirical p {:.4}",
+            significance.observed,
+            permutation.permutations,
+            permutation.shuffle,
+            significance.mean,
+            significance.z,
+            significance.p_value
+        );
+        save_significance(
+            format!("{}.significance.tsv", output_prefix),
+            &significance,
+            permutation,
+        )?;
+        stages.push(timer.finish());
+    }
+
+    let summary = Summary::new(&alignment, stages, started.elapsed().as_secs_f64());
+    log::info!(
+        "identity {:.4}, gap-compressed identity {:.4}",
+        summary.identity,
+        summary.gap_compressed_identity
     );
-    target_bed.insert(
+    save_summary(&output_prefix, &summary)?;
+    Ok(())
+}
+
+/// 读取并校验单体 BED，报告发现的问题，校验后为空时报错
+pub fn load_bed(path: &str, validation: ValidationMode) -> Result<Vec<MonRow>, HorScanError> {
+    let records = read_bed_records(path)?;
+    let (bed, issues) = validate_bed(records, validation);
+    report_issues(path, &issues, validation)?;
+    if bed.is_empty() {
+        return Err(HorScanError::EmptyInput {
+            path: path.to_string(),
+        });
+    }
+    Ok(bed)
+}
+
+/// 按起点排序，并在首位插入动态规划使用的空位行，返回样本名
+fn prepare_bed(bed: &mut Vec<MonRow>, name: &str) -> Result<String, HorScanError> {
+    if bed.is_empty() {
+        return Err(HorScanError::EmptyInput {
+            path: name.to_string(),
+        });
+    }
+    bed.sort_by_key(|row| row.start);
+    let sample = bed[0].sample.clone();
+    bed.insert(
         0,
         MonRow {
-            sample: target_sample.clone(),
+            sample: sample.clone(),
             start: 0,
             end: 0,
             mon: "-".to_string(),
         },
     );
-    // 调用HORSCAN的动态规划算法
-
-    println!("memory update linear score");
-    let score = horscan_global_linear_score_alignment_low(
-        source_bed.clone(),
-        target_bed.clone(),
-        mode.clone(),
-    )?;
-    let alignment_path = find_global_alignment_all_path_low(&score)?;
-    // 保存文件
-    save_all_path(
-        path.clone(),
-        source_bed.clone(),
-        target_bed.clone(),
-        &alignment_path,
-    )?;
-
-    // optimize alignment path
-    optimize_blast_file(path.clone())?;
-
-    // 保存 score matrix
-    // save_score_matrix(&score, path.clone())?;
-    Ok(())
+    Ok(sample)
 }
 
+/// 填充得分矩阵；给出 store 时从已保存的行块继续，并按行块写出检查点。
+/// 两侧开头至多 free_gaps 个空位不计分，使起点处于 HOR 单元不同位置的阵列可以错开对齐
+///
+/// 以 TILE 行为一个行带，行带内逐个分块填充，保证分块访问的局部性
 fn horscan_global_linear_score_alignment_low(
-    source_bed: Vec<MonRow>,
-    target_bed: Vec<MonRow>,
-    mode: Mode,
-) -> Result<Vec<Vec<i32>>, HorScanError> {
+    source_bed: &[MonRow],
+    target_bed: &[MonRow],
+    mode: &Mode,
+    options: &RunOptions,
+    free_gaps: usize,
+    store: Option<&TempStore>,
+) -> Result<ScoreMatrix, HorScanError> {
     let source_len = source_bed.len();
     let target_len = target_bed.len();
-    let mut score_matrix = vec![vec![0; target_len]; source_len];
+    let (source_ids, target_ids) = intern_labels(source_bed, target_bed);
+    let matrix_path = match store {
+        Some(store) => store.matrix_path(),
+        None => temp_matrix_path(),
+    };
+    let mut score_matrix =
+        ScoreMatrix::new(source_len, target_len, options.matrix_backend, &matrix_path)?;
     for i in 0..source_len {
-        score_matrix[i][0] = mode.gap_score * i as i32;
+        score_matrix.set(i, 0, mode.gap_score * i.saturating_sub(free_gaps) as i32);
     }
     for j in 0..target_len {
-        score_matrix[0][j] = mode.gap_score * j as i32;
+        score_matrix.set(0, j, mode.gap_score * j.saturating_sub(free_gaps) as i32);
+    }
+
+    // 已完成的行带（包括第 0 行）从检查点读回
+    let mut done = 0;
+    let mut checkpoint = None;
+    if let Some(store) = store {
+        done = store.restore_dp_rows(&mut score_matrix, TILE)?;
+        if done > 0 {
+            log::info!("resuming fill at row {} of {}", done, source_len);
+        }
+        let mut writer = store.dp_writer(target_len)?;
+        if done == 0 {
+            writer.push_row(&score_matrix.row(0))?;
+            done = 1;
+        }
+        checkpoint = Some(writer);
     }
 
     // 将连续的q或者t gap 的gap进行压缩
     // let mut last_path = 0;
 
-    for i in 1..source_len {
-        for j in 1..target_len {
-            let match_score = if source_bed[i].mon == target_bed[j].mon {
-                score_matrix[i - 1][j - 1] + mode.match_score
-            } else {
-                score_matrix[i - 1][j - 1] + mode.mismatch_score
-            };
-            let q_gap_score = score_matrix[i - 1][j] + mode.gap_score;
-            let t_gap_socre = score_matrix[i][j - 1] + mode.gap_score;
-            // if last_path == 1 {
-            //     q_gap_score = score_matrix[i - 1][j] + mode.gap_score / 2;
-            // } else if last_path == 2 {
-            //     t_gap_socre = score_matrix[i][j - 1] + mode.gap_score / 2;
-            // }
-            // 如果当前的gap是连续的，那么就添加half gap score
-            let last_score = vec![q_gap_score, t_gap_socre, match_score, 0];
-
-            let max_score = *last_score.iter().max().unwrap();
-            // last_path = last_score.iter().position(|&x| x == max_score).unwrap();
-
-            score_matrix[i][j] = max_score;
+    let mut progress = Progress::new("fill", source_len.saturating_sub(1) * target_len);
+    let mut band_start = done.max(1);
+    while band_start < source_len {
+        let band_end = ((band_start / TILE + 1) * TILE).min(source_len);
+        progress.update((band_start - 1) * target_len);
+        for tile_start in (0..target_len).step_by(TILE) {
+            let tile_end = (tile_start + TILE).min(target_len);
+            let band = source_ids.iter().enumerate().take(band_end).skip(band_start);
+            for (i, &source_id) in band {
+                let tile = target_ids.iter().enumerate().take(tile_end);
+                for (j, &target_id) in tile.skip(tile_start.max(1)) {
+                    let diagonal = score_matrix.get(i - 1, j - 1);
+                    let match_score = if source_id == target_id {
+                        diagonal + mode.match_score
+                    } else {
+                        diagonal + mode.mismatch_score
+                    };
+                    let q_gap_score = score_matrix.get(i - 1, j) + mode.gap_score;
+                    let t_gap_socre = score_matrix.get(i, j - 1) + mode.gap_score;
+                    // 如果当前的gap是连续的，那么就添加half gap score
+                    let max_score = q_gap_score.max(t_gap_socre).max(match_score).max(0);
+                    score_matrix.set(i, j, max_score);
+                }
+            }
+        }
+        if let Some(writer) = checkpoint.as_mut() {
+            for i in band_start..band_end {
+                writer.push_row(&score_matrix.row(i))?;
+            }
         }
+        band_start = band_end;
+    }
+    if let Some(writer) = checkpoint {
+        writer.finish()?;
     }
+    progress.finish();
     Ok(score_matrix)
 }
 
+/// 没有 `--prefix` 时 Mmap 后端的矩阵文件放在系统临时目录，进程内多次比对互不冲突
+fn temp_matrix_path() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir()
+        .join(format!(
+            "horscan-{}-{}.matrix",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ))
+        .display()
+        .to_string()
+}
+
 fn find_global_alignment_all_path_low(
-    score_matrix: &Vec<Vec<i32>>,
+    score_matrix: &ScoreMatrix,
 ) -> Result<Vec<(usize, usize, usize)>, HorScanError> {
     // find the path with the highest score
-    let mut i = score_matrix.len() - 1;
-    let mut j = score_matrix[0].len() - 1;
+    let mut i = score_matrix.rows() - 1;
+    let mut j = score_matrix.cols() - 1;
     let mut path = Vec::new();
-    // 0: MTH/MIS 1: INS 2:DEL
-    print!("source length: {} target length: {}\n", i, j);
+    // 0: MTH/MIS 1: DEL 2: INS
+    log::debug!("source length: {} target length: {}", i, j);
 
     // init: i j  find max score in  socre_matrix[i][:] and score_matrix[:][j]
-    let mut max_score = score_matrix[i][j]; // start from the bottom-right corner
+    let mut max_score = score_matrix.get(i, j); // start from the bottom-right corner
     let mut max_i = i;
     let mut max_j = j;
     for k in 0..i {
-        if score_matrix[k][j] > max_score {
-            max_score = score_matrix[k][j];
+        if score_matrix.get(k, j) > max_score {
+            max_score = score_matrix.get(k, j);
             max_i = k;
         }
     }
     for k in 0..j {
-        if score_matrix[i][k] > max_score {
-            max_score = score_matrix[i][k];
+        let score = score_matrix.get(i, k);
+        if score > max_score {
+            max_score = score;
             max_j = k;
         }
     }
     // add break path
     if max_i == i && max_j == j {
-        // path.push((i, j, 0 as usize));
-    } else if score_matrix[max_i][j] > score_matrix[i][max_j] {
+        // path.push((i, j, 0));
+    } else if score_matrix.get(max_i, j) > score_matrix.get(i, max_j) {
         for k in (max_i + 1..=i).rev() {
-            path.push((k, j, 1 as usize));
+            path.push((k, j, 1));
         }
         i = max_i;
     } else {
         for k in (max_j + 1..=j).rev() {
-            path.push((i, k, 2 as usize));
+            path.push((i, k, 2));
         }
         j = max_j;
     }
-    path.push((i, j, 0 as usize));
+    path.push((i, j, 0));
     while i > 0 && j > 0 {
-        let last_path: Vec<(usize, usize)> = vec![(i - 1, j - 1), (i - 1, j), (i, j - 1)];
-        let last_score: Vec<i32> = vec![
-            score_matrix[i - 1][j - 1],
-            score_matrix[i - 1][j],
-            score_matrix[i][j - 1],
+        let last_path: [(usize, usize); 3] = [(i - 1, j - 1), (i - 1, j), (i, j - 1)];
+        let last_score: [i32; 3] = [
+            score_matrix.get(i - 1, j - 1),
+            score_matrix.get(i - 1, j),
+            score_matrix.get(i, j - 1),
         ];
-        let max_score = last_score.iter().max().unwrap();
-        let max_index = last_score.iter().position(|&x| x == *max_score).unwrap();
+        // 取分数最大的前驱，分数相同时优先对角线
+        let mut max_index = 0;
+        for (k, &score) in last_score.iter().enumerate() {
+            if score > last_score[max_index] {
+                max_index = k;
+            }
+        }
         i = last_path[max_index].0;
         j = last_path[max_index].1;
         if i > 0 && j > 0 {
-            path.push((i, j, max_index as usize));
+            path.push((i, j, max_index));
         }
     }
     if i > 0 {
@@ -192,15 +530,15 @@ fn find_global_alignment_all_path_low(
     Ok(path)
 }
 
-fn save_all_path(
-    file_path: String,
-    source_bed: Vec<MonRow>,
-    target_bed: Vec<MonRow>,
-    alignment_path: &Vec<(usize, usize, usize)>,
-) -> Result<(), HorScanError> {
-    let mut file = File::create(file_path).expect("无法创建文件");
-    // 0: MTH/MIS 1: INS 2:DEL
-    let alignment_type = vec!["MTH", "INS", "DEL", "MIS"];
+/// 将回溯路径转换为比对行（按序列正向），跳过 (0, 0) 起点
+fn path_to_rows(
+    source_bed: &[MonRow],
+    target_bed: &[MonRow],
+    alignment_path: &[(usize, usize, usize)],
+) -> Result<Vec<MonAlignmentRow>, HorScanError> {
+    // 0: MTH/MIS 1: target 为空位 (DEL) 2: source 为空位 (INS)
+    let alignment_type = ["MTH", "DEL", "INS", "MIS"];
+    let mut rows = Vec::with_capacity(alignment_path.len());
     for &path in alignment_path.iter().rev() {
         let mut source_row = source_bed[path.0].clone();
         let mut target_row = target_bed[path.1].clone();
@@ -230,55 +568,112 @@ fn save_all_path(
             }
         } else {
             // 抛出错误 AlignmentError
-            return Err(HorScanError::AlignmentError("未知的对齐类型".to_string()));
+            return Err(HorScanError::AlignmentError(format!(
+                "unknown traceback step {} at ({}, {})",
+                path.2, path.0, path.1
+            )));
         }
-        // 输出模式
-        // writeln!(
-        //     file,
-        //     "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-        //     source_row.sample,
-        //     source_row.start,
-        //     source_row.end,
-        //     source_row.mon,
-        //     target_row.sample,
-        //     target_row.start,
-        //     target_row.end,
-        //     target_row.mon,
-        //     alignment_type,
-        // )?;
-        // 调试模式
-        writeln!(
-            file,
-            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-            source_row.sample,
-            source_row.start,
-            source_row.end,
-            source_row.mon,
-            target_row.sample,
-            target_row.start,
-            target_row.end,
-            target_row.mon,
-            alignment_type,
-            path.0,
-            path.1,
-            path.2,
-        )?;
+        rows.push(MonAlignmentRow {
+            source: source_row.sample,
+            source_start: source_row.start,
+            source_end: source_row.end,
+            source_mon: source_row.mon,
+            target: target_row.sample,
+            target_start: target_row.start,
+            target_end: target_row.end,
+            target_mon: target_row.mon,
+            align_type: alignment_type.to_string(),
+        });
     }
-    Ok(())
+    Ok(rows)
 }
 
-// 将 score 保存为numpy能够直接打开的格式
-// fn save_score_matrix(
-//     score_matrix: &Vec<Vec<i32>>,
-//     output_path: String,
-// ) -> Result<(), HorScanError> {
-//     let file = File::create(output_path + ".mat")?;
-//     let mut writer = BufWriter::new(file);
-//     for row in score_matrix {
-//         for score in row {
-//             write!(writer, "{} ", score)?; // 使用空格分隔每个分数
-//         }
-//         writeln!(writer)?;
-//     }
-//     Ok(())
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{bed, TestDir};
+
+    #[test]
+    fn test_align_readme_example() -> Result<(), HorScanError> {
+        let source = bed("CHM13#chrX", "ABDEFGHIJK");
+        let target = bed("CHM1#chrX", "ABCDEOGHJK");
+        let mode = Mode::from_params(&[10, 4, 2]);
+        let alignment = align(&source, &target, &mode)?;
+        let types: Vec<&str> = alignment
+            .rows
+            .iter()
+            .map(|r| r.align_type.as_str())
+            .collect();
+        assert_eq!(
+            types,
+            ["MTH", "MTH", "INS", "MTH", "MTH", "MIS", "MTH", "MTH", "DEL", "MTH", "MTH"]
+        );
+        assert_eq!(alignment.score, 8 * 10 - 4 - 2 * 2);
+        assert_eq!(alignment.score, alignment_score(&alignment.rows, &mode));
+        Ok(())
+    }
+
+    #[test]
+    fn test_align_phase() -> Result<(), HorScanError> {
+        // target 从 HOR 单元的第 5 个单体开始
+        let source = bed("CHM13#chrX", &"ABCDEFGH".repeat(4));
+        let target = bed("CHM1#chrX", &("EFGH".to_string() + &"ABCDEFGH".repeat(3)));
+        let mode = Mode::from_params(&[10, 4, 2]);
+        let options = RunOptions {
+            phase: true,
+            ..RunOptions::default()
+        };
+        let alignment = align_with_options(&source, &target, &mode, &options)?;
+        assert_eq!(alignment.phase.as_ref().map(|p| p.offset), Some(4));
+        // 相位差表现为开头普通的空位行：source 的 A B C D 没有对应单体，其余全部匹配
+        let types: Vec<&str> = alignment.rows.iter().map(|r| r.align_type.as_str()).collect();
+        assert_eq!(types[..4], ["DEL"; 4]);
+        assert!(types[4..].iter().all(|t| *t == "MTH"));
+        assert_eq!(alignment.score, alignment_score(&alignment.rows, &mode));
+
+        // 开头至多 free_gaps 个空位不计分
+        let mut source_bed = source[..8].to_vec();
+        let mut target_bed = target[..8].to_vec();
+        prepare_bed(&mut source_bed, "source")?;
+        prepare_bed(&mut target_bed, "target")?;
+        let matrix = horscan_global_linear_score_alignment_low(
+            &source_bed,
+            &target_bed,
+            &mode,
+            &RunOptions::default(),
+            7,
+            None,
+        )?;
+        assert_eq!((matrix.get(7, 0), matrix.get(0, 7), matrix.get(8, 0)), (0, 0, -2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resumed_run_still_dumps_matrix() -> Result<(), HorScanError> {
+        let dir = TestDir::new("resume_dump");
+        let source = bed("CHM13#chrX", "ABDEFGHIJK");
+        let target = bed("CHM1#chrX", "ABCDEOGHJK");
+        let dump = dir.path("score.npy");
+        let options = RunOptions {
+            temp_prefix: Some(dir.path("run").display().to_string()),
+            dump_matrix: Some(dump.display().to_string()),
+            ..RunOptions::default()
+        };
+        let mode = Mode::from_params(&[10, 4, 2]);
+        let first = align_with_options(&source, &target, &mode, &options)?;
+        let full = std::fs::read(&dump)?;
+        std::fs::remove_file(&dump)?;
+        // 已有保存的原始比对，但输出矩阵需要重新填充
+        let second = align_with_options(&source, &target, &mode, &options)?;
+        assert!(second.stages.iter().any(|stage| stage.stage == "fill"));
+        assert_eq!(std::fs::read(&dump)?, full);
+        assert_eq!(second.score, first.score);
+        Ok(())
+    }
+
+    #[test]
+    fn test_align_empty_input_is_error() {
+        let source = bed("CHM13#chrX", "AB");
+        assert!(align(&source, &[], &Mode::default_mode()).is_err());
+    }
+}
@@ -13,6 +13,31 @@ pub struct Mode {
     // pub jump_max: usize,
 }
 
+impl Mode {
+    /// 默认打分参数，与 README 中的示例保持一致
+    pub fn default_mode() -> Mode {
+        Mode {
+            match_score: 4,
+            mismatch_score: -5,
+            gap_score: -2,
+        }
+    }
+
+    /// 由命令行 `MATCH MISMATCH GAP` 构建打分参数，罚分以正数输入
+    pub fn from_params(params: &[i32]) -> Mode {
+        if params.len() < 3 {
+            return Mode::default_mode();
+        }
+        Mode {
+            // match_method: params[0],
+            match_score: params[0],
+            mismatch_score: -params[1],
+            gap_score: -params[2],
+            // jump_max: params[4] as usize,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MonRow {
     pub sample: String,
@@ -31,144 +56,424 @@ pub struct MonAlignmentRow {
     pub target_start: i32,
     pub target_end: i32,
     pub target_mon: String,
-    pub align_type: String
+    pub align_type: String,
 }
 
 // 修正错误枚举定义
 #[derive(Debug, Error)]
 pub enum HorScanError {
-    // 文件操作错误（自动转换 std::io::Error）
-    #[error("IO error: {0}")]
+    // 未附带路径的文件操作错误（自动转换 std::io::Error）
+    #[error("I/O error: {0}")]
     Io(#[from] io::Error),
 
-    // BED文件解析错误
-    #[error("Parse error: {0}")]
-    ParseError(String),
+    // 输入文件无法打开或读取
+    #[error("cannot read {path}: {source}")]
+    Read { path: String, source: io::Error },
+
+    // 输出文件无法创建或写入
+    #[error("cannot write {path}: {source}")]
+    Write { path: String, source: io::Error },
+
+    // 输入文件格式错误，行号与列号均从 1 开始
+    #[error("{path}:{line}:{column}: {reason}")]
+    Parse {
+        path: String,
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+
+    // 输入文件中没有任何记录
+    #[error("{path}: no records found")]
+    EmptyInput { path: String },
+
+    // 严格校验模式下输入文件存在问题
+    #[error("{path}: {count} validation problem(s), rerun with --validate lenient to repair")]
+    InvalidInput { path: String, count: usize },
+
+    // 比对文件与输入单体不一致
+    #[error("{path}: {count} problem(s), not a valid alignment of the given BEDs")]
+    InvalidAlignment { path: String, count: usize },
+
+    // 命令行参数或参数值错误
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
 
     // 动态规划逻辑错误
-    #[error("Alignment error: {0}")]
-    AlignmentError(String)
+    #[error("alignment error: {0}")]
+    AlignmentError(String),
+
+    // batch 中部分任务失败
+    #[error("{failed} of {total} pairs failed")]
+    PairsFailed { failed: usize, total: usize },
 }
 
-/// 辅助函数：将字符串解析为特定类型，处理转换错误
-fn parse_field<T: std::str::FromStr>(s: &str, field_name: &str) -> Result<T, HorScanError> {
-    s.parse()
-        .map_err(|_| HorScanError::ParseError(format!("无法解析{}字段: '{}'", field_name, s)))
+impl HorScanError {
+    /// 每类错误对应的进程退出码（参照 sysexits.h），便于流程管理工具区分
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            HorScanError::PairsFailed { .. } => 1,
+            HorScanError::InvalidValue(_) => 64,
+            HorScanError::Parse { .. }
+            | HorScanError::EmptyInput { .. }
+            | HorScanError::InvalidInput { .. }
+            | HorScanError::InvalidAlignment { .. } => 65,
+            HorScanError::Read { .. } => 66,
+            HorScanError::AlignmentError(_) => 70,
+            HorScanError::Write { .. } => 73,
+            HorScanError::Io(_) => 74,
+        }
+    }
 }
 
-/// 读取BED文件并转换为MonRow结构体
-pub fn read_bed_file<P: AsRef<Path>>(path: P) -> Result<Vec<MonRow>, HorScanError> {
-    let file = File::open(path)?;
+fn path_string<P: AsRef<Path>>(path: P) -> String {
+    path.as_ref().display().to_string()
+}
+
+/// 读取文本文件中的数据行，跳过注释行和空行，返回 (行号, 内容)
+pub fn read_data_lines<P: AsRef<Path>>(path: P) -> Result<Vec<(usize, String)>, HorScanError> {
+    let read_error = |source| HorScanError::Read {
+        path: path_string(&path),
+        source,
+    };
+    let file = File::open(&path).map_err(read_error)?;
     let reader = BufReader::new(file);
-    let mut rows = Vec::new();
+    let mut lines = Vec::new();
 
-    for line_result in reader.lines() {
-        let line = line_result?;
+    for (idx, line_result) in reader.lines().enumerate() {
+        let line = line_result.map_err(read_error)?;
         // 跳过注释行和空行
         if line.starts_with("track") || line.starts_with('#') || line.trim().is_empty() {
             continue;
         }
+        lines.push((idx + 1, line));
+    }
+    Ok(lines)
+}
 
-        let parts: Vec<&str> = line.split('\t').collect();
-        if parts.len() < 4 {
-            return Err(HorScanError::ParseError(format!(
-                "行 '{}' 需要至少4列",
-                line
-            )));
-        }
+/// 创建输出文件并写入内容，任何写入错误都附带输出路径
+pub fn write_output<P, F>(path: P, write: F) -> Result<(), HorScanError>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let result = File::create(&path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.flush()
+    });
+    result.map_err(|source| HorScanError::Write {
+        path: path_string(&path),
+        source,
+    })
+}
+
+/// 按制表符切分一行，列数不足时返回带位置的解析错误
+pub fn split_columns<'a>(
+    line: &'a str,
+    min_columns: usize,
+    path: &str,
+    line_number: usize,
+) -> Result<Vec<&'a str>, HorScanError> {
+    let parts: Vec<&str> = line.split('\t').collect();
+    if parts.len() < min_columns {
+        return Err(HorScanError::Parse {
+            path: path.to_string(),
+            line: line_number,
+            column: parts.len() + 1,
+            reason: format!(
+                "expected at least {} tab-separated columns, found {}",
+                min_columns,
+                parts.len()
+            ),
+        });
+    }
+    Ok(parts)
+}
+
+/// 辅助函数：将字符串解析为特定类型，处理转换错误
+pub fn parse_field<T: std::str::FromStr>(
+    s: &str,
+    field_name: &str,
+    path: &str,
+    line: usize,
+    column: usize,
+) -> Result<T, HorScanError> {
+    s.parse().map_err(|_| HorScanError::Parse {
+        path: path.to_string(),
+        line,
+        column,
+        reason: format!("cannot parse {} field '{}'", field_name, s),
+    })
+}
+
+/// 读取BED文件并转换为MonRow结构体
+pub fn read_bed_file<P: AsRef<Path>>(path: P) -> Result<Vec<MonRow>, HorScanError> {
+    Ok(read_bed_records(path)?
+        .into_iter()
+        .map(|(_, row)| row)
+        .collect())
+}
+
+/// 读取BED文件，同时保留每个单体所在的行号，用于校验时定位问题
+pub fn read_bed_records<P: AsRef<Path>>(path: P) -> Result<Vec<(usize, MonRow)>, HorScanError> {
+    let name = path_string(&path);
+    let mut rows = Vec::new();
+
+    for (line_number, line) in read_data_lines(&path)? {
+        let parts = split_columns(&line, 4, &name, line_number)?;
 
         // 解析前三个字段为i32
         let sample = parts[0].to_string();
-        let start = parse_field(parts[1], "start")?;
-        let end = parse_field(parts[2], "end")?;
+        let start = parse_field(parts[1], "start", &name, line_number, 2)?;
+        let end = parse_field(parts[2], "end", &name, line_number, 3)?;
 
         // 第四个字段保持String类型
         let mon = parts[3].to_string();
 
-        rows.push(MonRow {
-            sample,
-            start,
-            end,
-            mon,
-        });
+        rows.push((
+            line_number,
+            MonRow {
+                sample,
+                start,
+                end,
+                mon,
+            },
+        ));
     }
     Ok(rows)
 }
 
+/// 比对文件的第一行，记录文件格式的版本。版本 2 起 INS 表示 source 一侧为空位、DEL 表示 target
+/// 一侧为空位；更早的版本没有这一行，且 INS/DEL 的含义与此相反
+pub const ALIGNMENT_FORMAT: &str = "#horscan-alignment\tversion=2";
+
+/// 文件的第一行是否为 ALIGNMENT_FORMAT
+fn has_format_line(path: &Path) -> Result<bool, HorScanError> {
+    let read_error = |source| HorScanError::Read {
+        path: path_string(path),
+        source,
+    };
+    let file = File::open(path).map_err(read_error)?;
+    let mut first = String::new();
+    BufReader::new(file)
+        .read_line(&mut first)
+        .map_err(read_error)?;
+    Ok(first.trim_end() == ALIGNMENT_FORMAT)
+}
+
+/// 空位行的 INS/DEL 标注方式：Some(true) 为旧含义（INS 而 target 为空位，或 DEL 而 source 为空位），
+/// Some(false) 为当前含义，其余的行为 None
+fn legacy_gap(row: &MonAlignmentRow) -> Option<bool> {
+    let (source_gap, target_gap) = (row.source_mon == "-", row.target_mon == "-");
+    match (row.align_type.as_str(), source_gap, target_gap) {
+        ("INS", false, true) | ("DEL", true, false) => Some(true),
+        ("INS", true, false) | ("DEL", false, true) => Some(false),
+        _ => None,
+    }
+}
+
 pub fn read_blast_file<P: AsRef<Path>>(path: P) -> Result<Vec<MonAlignmentRow>, HorScanError> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut rows = Vec::new();
+    Ok(read_blast_records(path)?
+        .into_iter()
+        .map(|(_, row)| row)
+        .collect())
+}
 
-    for line_result in reader.lines() {
-        let line = line_result?;
-        // 跳过注释行和空行
-        if line.starts_with("track") || line.starts_with('#') || line.trim().is_empty() {
-            continue;
-        }
+/// 读取比对文件，同时保留每一行的行号。没有格式行的文件中空位行全部按旧含义标注时，
+/// 给出警告并整体改正；新旧两种标注混在一起时无法判断，返回 InvalidAlignment
+pub fn read_blast_records<P: AsRef<Path>>(
+    path: P,
+) -> Result<Vec<(usize, MonAlignmentRow)>, HorScanError> {
+    let name = path_string(&path);
+    let mut rows = Vec::new();
 
-        let parts: Vec<&str> = line.split('\t').collect();
-        if parts.len() < 9 {
-            return Err(HorScanError::ParseError(format!(
-                "行 '{}' 需要至少9列",
-                line
-            )));
-        }
+    for (line_number, line) in read_data_lines(&path)? {
+        let parts = split_columns(&line, 9, &name, line_number)?;
 
         // 解析前三个字段为i32
         let source = parts[0].to_string();
-        let source_start = parse_field(parts[1], "start")?;
-        let source_end = parse_field(parts[2], "end")?;
+        let source_start = parse_field(parts[1], "source start", &name, line_number, 2)?;
+        let source_end = parse_field(parts[2], "source end", &name, line_number, 3)?;
         let source_mon = parts[3].to_string();
         let target = parts[4].to_string();
-        let target_start = parse_field(parts[5], "start")?;
-        let target_end = parse_field(parts[6], "end")?;
+        let target_start = parse_field(parts[5], "target start", &name, line_number, 6)?;
+        let target_end = parse_field(parts[6], "target end", &name, line_number, 7)?;
         let target_mon = parts[7].to_string();
         let align_type = parts[8].to_string();
 
         // 第四个字段保持String类型
         // let mon = parts[3].to_string();
 
-        rows.push(MonAlignmentRow {
-            source,
-            source_start,
-            source_end,
-            source_mon,
-            target,
-            target_start,
-            target_end,
-            target_mon,
-            align_type,
-        });
+        rows.push((
+            line_number,
+            MonAlignmentRow {
+                source,
+                source_start,
+                source_end,
+                source_mon,
+                target,
+                target_start,
+                target_end,
+                target_mon,
+                align_type,
+            },
+        ));
+    }
+
+    if !has_format_line(path.as_ref())? {
+        let count = |legacy: bool| {
+            rows.iter()
+                .filter(|(_, row)| legacy_gap(row) == Some(legacy))
+                .count()
+        };
+        let (legacy, current) = (count(true), count(false));
+        if legacy > 0 && current > 0 {
+            log::error!(
+                "{}: no format line and the gap rows mix both INS/DEL conventions ({} old, {} current)",
+                name,
+                legacy,
+                current
+            );
+            return Err(HorScanError::InvalidAlignment {
+                path: name,
+                count: legacy.min(current),
+            });
+        }
+        if legacy > 0 {
+            log::warn!(
+                "{}: written by an older HORSCAN with INS and DEL swapped, relabelling {} gap row(s)",
+                name,
+                legacy
+            );
+            for (_, row) in rows.iter_mut() {
+                match (legacy_gap(row), row.align_type.as_str()) {
+                    (Some(true), "INS") => row.align_type = "DEL".to_string(),
+                    (Some(true), _) => row.align_type = "INS".to_string(),
+                    _ => {}
+                }
+            }
+        }
     }
     Ok(rows)
 }
 
-pub fn save_blast_file(rows: &Vec<MonAlignmentRow>, output_path: String) -> Result<(), HorScanError> {
-    let file = File::create(output_path)?;
-    let mut writer = BufWriter::new(file);
-    for row in rows {
-        writeln!(writer, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", 
-                 row.source, row.source_start, row.source_end, row.source_mon, 
-                 row.target, row.target_start, row.target_end, row.target_mon, 
-                 row.align_type)?;
-
-    }
-    Ok(())
+pub fn save_blast_file(rows: &[MonAlignmentRow], output_path: String) -> Result<(), HorScanError> {
+    save_blast_file_with_header(rows, &[], output_path)
+}
 
+/// 写出比对文件，开头为格式行和若干 `#` 注释行（读取时跳过）
+pub fn save_blast_file_with_header(
+    rows: &[MonAlignmentRow],
+    header: &[String],
+    output_path: String,
+) -> Result<(), HorScanError> {
+    write_output(&output_path, |writer| {
+        writeln!(writer, "{}", ALIGNMENT_FORMAT)?;
+        for line in header {
+            writeln!(writer, "{}", line)?;
+        }
+        for row in rows {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                row.source,
+                row.source_start,
+                row.source_end,
+                row.source_mon,
+                row.target,
+                row.target_start,
+                row.target_end,
+                row.target_mon,
+                row.align_type
+            )?;
+        }
+        Ok(())
+    })
 }
 // 测试read_bed函数
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutil::TestDir;
     #[test]
     fn test_read_bed() -> Result<(), HorScanError> {
-        let bed_path = "/home/wan/projects/HORSCAN/test_output/G100_monomer.bed";
-        let result = read_bed_file(bed_path)?;
+        let dir = TestDir::new("read_bed");
+        let bed_path = dir.path("test.bed");
+        std::fs::write(
+            &bed_path,
+            "track name=test\nCHM13#chrX\t1000\t1170\tA\n# comment\nCHM13#chrX\t1171\t1341\tB\n",
+        )?;
+        let result = read_bed_file(&bed_path)?;
         // 逐行打印
-        for row in result {
+        for row in &result {
             println!("{:?}\n", row);
         }
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1].start, 1171);
+        assert_eq!(result[1].mon, "B");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_bed_parse_error_location() -> Result<(), HorScanError> {
+        let dir = TestDir::new("read_bed_error");
+        let bed_path = dir.path("error.bed");
+        std::fs::write(&bed_path, "# header\nchrX\t0\t170\tA\nchrX\t171\tabc\tB\n")?;
+        match read_bed_file(&bed_path) {
+            Err(HorScanError::Parse { line, column, .. }) => {
+                assert_eq!((line, column), (3, 3));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        match read_bed_file(dir.path("missing.bed")) {
+            Err(e @ HorScanError::Read { .. }) => assert_eq!(e.exit_code(), 66),
+            other => panic!("unexpected result {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_alignment_format_and_legacy_labels() -> Result<(), HorScanError> {
+        let dir = TestDir::new("alignment_format");
+        let old = "s\t0\t170\tA\tt\t0\t170\tA\tMTH\n\
+                   s\t171\t341\tB\tt\t170\t170\t-\tINS\n\
+                   s\t171\t171\t-\tt\t171\t341\tC\tDEL\n";
+        let legacy = dir.path("legacy.alignment");
+        std::fs::write(&legacy, old)?;
+        let rows = read_blast_file(&legacy)?;
+        let types: Vec<&str> = rows.iter().map(|r| r.align_type.as_str()).collect();
+        assert_eq!(types, ["MTH", "DEL", "INS"]);
+
+        // 新文件带格式行，按原样读回
+        // 没有格式行但已经是当前标注的文件原样读入，新旧混用时报错
+        let new = "s\t0\t170\tA\tt\t0\t170\tA\tMTH\n\
+                   s\t171\t341\tB\tt\t170\t170\t-\tDEL\n\
+                   s\t171\t171\t-\tt\t171\t341\tC\tINS\n";
+        std::fs::write(&legacy, new)?;
+        let types: Vec<String> = read_blast_file(&legacy)?
+            .into_iter()
+            .map(|r| r.align_type)
+            .collect();
+        assert_eq!(types, ["MTH", "DEL", "INS"]);
+        let mixed = "s\t0\t170\tA\tt\t0\t170\tA\tMTH\n\
+                     s\t171\t341\tB\tt\t170\t170\t-\tDEL\n\
+                     s\t171\t171\t-\tt\t171\t341\tC\tDEL\n";
+        std::fs::write(&legacy, mixed)?;
+        assert!(matches!(
+            read_blast_file(&legacy),
+            Err(HorScanError::InvalidAlignment { count: 1, .. })
+        ));
+
+        let current = dir.path("current.alignment").display().to_string();
+        save_blast_file(&rows, current.clone())?;
+        let text = std::fs::read_to_string(&current)?;
+        assert_eq!(text.lines().next(), Some(ALIGNMENT_FORMAT));
+        let types_again: Vec<String> = read_blast_file(&current)?
+            .into_iter()
+            .map(|r| r.align_type)
+            .collect();
+        assert_eq!(types_again, types);
         Ok(())
     }
 }
@@ -0,0 +1,250 @@
+use crate::horscan::load_bed;
+use crate::io::{read_data_lines, split_columns, write_output, HorScanError, MonRow};
+use crate::validate::ValidationMode;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+/// 两份注释中的单体至少重叠较短者的该比例时才视为同一个单体
+const MIN_OVERLAP: f64 = 0.5;
+/// 支持度低于该值的对应关系给出警告
+const MIN_SUPPORT: f64 = 0.9;
+
+/// 单体标签映射：读取 BED 后将同义标签统一为一个名称，映射中没有的标签保持不变
+#[derive(Clone, Debug, Default)]
+pub struct LabelMap {
+    map: HashMap<String, String>,
+}
+
+impl LabelMap {
+    /// 读取映射文件：制表符分隔，第 1 列为原标签，第 2 列为统一后的标签，其余列忽略；
+    /// 同一原标签映射到不同标签时报错
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<LabelMap, HorScanError> {
+        let name = path.as_ref().display().to_string();
+        let mut map: HashMap<String, String> = HashMap::new();
+        for (line_number, line) in read_data_lines(&path)? {
+            let parts = split_columns(&line, 2, &name, line_number)?;
+            let (from, to) = (parts[0].trim(), parts[1].trim());
+            match map.get(from) {
+                Some(existing) if existing != to => {
+                    return Err(HorScanError::Parse {
+                        path: name,
+                        line: line_number,
+                        column: 2,
+                        reason: format!(
+                            "label '{}' is already mapped to '{}', cannot also map it to '{}'",
+                            from, existing, to
+                        ),
+                    });
+                }
+                _ => {
+                    map.insert(from.to_string(), to.to_string());
+                }
+            }
+        }
+        Ok(LabelMap { map })
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// 替换单体标签，返回被替换的单体数
+    pub fn apply(&self, bed: &mut [MonRow]) -> usize {
+        let mut renamed = 0;
+        for row in bed.iter_mut() {
+            if let Some(to) = self.map.get(&row.mon) {
+                if *to != row.mon {
+                    row.mon = to.clone();
+                    renamed += 1;
+                }
+            }
+        }
+        renamed
+    }
+}
+
+/// 读取映射文件并应用到各组单体上，记录替换数量
+pub fn apply_label_map(path: &str, beds: &mut [&mut Vec<MonRow>]) -> Result<(), HorScanError> {
+    let map = LabelMap::read(path)?;
+    for bed in beds.iter_mut() {
+        let renamed = map.apply(bed);
+        log::info!(
+            "{}: {} of {} monomers relabelled ({} labels in the map)",
+            path,
+            renamed,
+            bed.len(),
+            map.len()
+        );
+    }
+    Ok(())
+}
+
+/// 学到的一条标签对应关系
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelPair {
+    /// target 注释中的标签
+    pub from: String,
+    /// source 注释中的标签
+    pub to: String,
+    /// 两者重叠的单体数
+    pub pairs: usize,
+    /// pairs 占 from 标签所有已配对单体的比例
+    pub support: f64,
+}
+
+/// 按坐标将同一组装的两份注释中的单体配对：每个 target 单体取与其重叠最多的 source 单体
+pub fn pair_monomers<'a>(source: &'a [MonRow], target: &'a [MonRow]) -> Vec<(&'a MonRow, &'a MonRow)> {
+    let mut source: Vec<&MonRow> = source.iter().collect();
+    let mut target: Vec<&MonRow> = target.iter().collect();
+    source.sort_by(|a, b| (&a.sample, a.start).cmp(&(&b.sample, b.start)));
+    target.sort_by(|a, b| (&a.sample, a.start).cmp(&(&b.sample, b.start)));
+
+    let mut pairs = Vec::new();
+    let mut first = 0;
+    for t in target {
+        // source 按起点排序，跳过已经完全在当前 target 单体之前的
+        while first < source.len()
+            && (source[first].sample < t.sample
+                || (source[first].sample == t.sample && source[first].end <= t.start))
+        {
+            first += 1;
+        }
+        let best = source[first..]
+            .iter()
+            .take_while(|s| s.sample == t.sample && s.start < t.end)
+            .map(|s| (s.end.min(t.end) - s.start.max(t.start), *s))
+            .max_by_key(|(overlap, _)| *overlap);
+        if let Some((overlap, s)) = best {
+            let shorter = (s.end - s.start).min(t.end - t.start).max(1);
+            if overlap as f64 >= MIN_OVERLAP * shorter as f64 {
+                pairs.push((s, t));
+            }
+        }
+    }
+    pairs
+}
+
+/// 由配对的单体提出一一对应的标签映射：按配对数从多到少贪心选取，每个标签只用一次
+pub fn learn_label_map(source: &[MonRow], target: &[MonRow]) -> Vec<LabelPair> {
+    let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    let mut totals: HashMap<&str, usize> = HashMap::new();
+    for (s, t) in pair_monomers(source, target) {
+        *counts.entry((t.mon.as_str(), s.mon.as_str())).or_insert(0) += 1;
+        *totals.entry(t.mon.as_str()).or_insert(0) += 1;
+    }
+    let mut candidates: Vec<((&str, &str), usize)> = counts.into_iter().collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut used_from: HashSet<&str> = HashSet::new();
+    let mut used_to: HashSet<&str> = HashSet::new();
+    let mut map = Vec::new();
+    for ((from, to), pairs) in candidates {
+        if used_from.contains(from) || used_to.contains(to) {
+            continue;
+        }
+        used_from.insert(from);
+        used_to.insert(to);
+        map.push(LabelPair {
+            from: from.to_string(),
+            to: to.to_string(),
+            pairs,
+            support: pairs as f64 / totals[from] as f64,
+        });
+    }
+    map.sort_by(|a, b| a.from.cmp(&b.from));
+    map
+}
+
+/// learn-map 命令：比对同一组装的两份注释，写出将 target 标签映射为 source 标签的
+/// `<prefix>.labels.tsv`，可直接用作 `--label-map`
+pub fn run_learn_map(
+    source_path: &str,
+    target_path: &str,
+    output_prefix: &str,
+    validation: ValidationMode,
+) -> Result<(), HorScanError> {
+    let source = load_bed(source_path, validation)?;
+    let target = load_bed(target_path, validation)?;
+    let paired = pair_monomers(&source, &target).len();
+    log::info!(
+        "{} of {} target monomers overlap a source monomer",
+        paired,
+        target.len()
+    );
+    if paired == 0 {
+        return Err(HorScanError::InvalidValue(format!(
+            "no monomers of {} overlap {}, are they annotations of the same assembly?",
+            target_path, source_path
+        )));
+    }
+
+    let map = learn_label_map(&source, &target);
+    for pair in map.iter().filter(|pair| pair.support < MIN_SUPPORT) {
+        log::warn!(
+            "{} -> {} is supported by only {:.1}% of its monomers",
+            pair.from,
+            pair.to,
+            pair.support * 100.0
+        );
+    }
+    let mapped: HashSet<&str> = map.iter().map(|pair| pair.from.as_str()).collect();
+    let unmapped: HashSet<&str> = target
+        .iter()
+        .map(|row| row.mon.as_str())
+        .filter(|label| !mapped.contains(label))
+        .collect();
+    if !unmapped.is_empty() {
+        log::warn!(
+            "{} target label(s) have no one-to-one counterpart and are left unchanged",
+            unmapped.len()
+        );
+    }
+
+    write_output(format!("{}.labels.tsv", output_prefix), |writer| {
+        writeln!(writer, "#from\tto\tpairs\tsupport")?;
+        for pair in &map {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{:.4}",
+                pair.from, pair.to, pair.pairs, pair.support
+            )?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::bed_words;
+
+    #[test]
+    fn test_learn_and_apply_label_map() {
+        let source = bed_words("chrX", "M1 M2 M3 M1 M2 M3 M1 M2");
+        let mut target = bed_words("chrX", "m3 m1 m2 m3 m1 m2 m3 m9");
+        // 坐标稍有偏移也能配对
+        for row in target.iter_mut() {
+            row.start += 20;
+            row.end += 20;
+        }
+        let map = learn_label_map(&source, &target);
+        let found: Vec<(&str, &str, usize)> = map
+            .iter()
+            .map(|p| (p.from.as_str(), p.to.as_str(), p.pairs))
+            .collect();
+        // m9 与 M2 重叠，但 M2 已对应 m2
+        assert_eq!(found, [("m1", "M2", 2), ("m2", "M3", 2), ("m3", "M1", 3)]);
+
+        let label_map = LabelMap {
+            map: map.into_iter().map(|p| (p.from, p.to)).collect(),
+        };
+        assert_eq!(label_map.apply(&mut target), 7);
+        assert_eq!(target[0].mon, "M1");
+        assert_eq!(target[7].mon, "m9");
+    }
+}
@@ -0,0 +1,65 @@
+//! HORSCAN：基于多层动态规划的着丝粒 HOR 水平结构变异比对
+//!
+//! 命令行工具只是本库的一层包装，流程中可以直接在内存中调用比对：
+//!
+//! ```
+//! use horscan::{align, Mode, MonRow};
+//!
+//! let mon = |sample: &str, start: i32, label: &str| MonRow {
+//!     sample: sample.to_string(),
+//!     start,
+//!     end: start + 170,
+//!     mon: label.to_string(),
+//! };
+//! let source: Vec<MonRow> = ["A", "B", "C"]
+//!     .iter()
+//!     .enumerate()
+//!     .map(|(i, label)| mon("CHM13#chrX", 171 * i as i32, label))
+//!     .collect();
+//! let target: Vec<MonRow> = ["A", "D", "C"]
+//!     .iter()
+//!     .enumerate()
+//!     .map(|(i, label)| mon("CHM1#chrX", 171 * i as i32, label))
+//!     .collect();
+//! let alignment = align(&source, &target, &Mode::from_params(&[10, 4, 2])).unwrap();
+//! assert_eq!(alignment.count.mth, 2);
+//! assert_eq!(alignment.count.mis, 1);
+//! ```
+
+pub mod batch;
+pub mod checkpoint;
+pub mod config;
+pub mod diff;
+pub mod eval;
+pub mod hor;
+pub mod horscan;
+pub mod io;
+pub mod labels;
+pub mod liftover;
+pub mod logging;
+pub mod matrix;
+pub mod npy;
+pub mod optimize;
+pub mod phylo;
+pub mod region;
+pub mod rng;
+pub mod score;
+pub mod significance;
+pub mod simulate;
+pub mod summary;
+pub mod sv;
+#[cfg(test)]
+pub(crate) mod testutil;
+pub mod validate;
+pub mod window;
+
+pub use crate::config::RunConfig;
+pub use crate::horscan::{align, align_with_options, alignment_score, Alignment, RunOptions};
+pub use crate::io::{
+    read_bed_file, read_blast_file, save_blast_file, HorScanError, Mode, MonAlignmentRow, MonRow,
+};
+pub use crate::labels::LabelMap;
+pub use crate::matrix::MatrixBackend;
+pub use crate::optimize::TypeCount;
+pub use crate::region::Region;
+pub use crate::validate::{validate_bed, ValidationMode};
@@ -0,0 +1,233 @@
+use crate::io::{
+    parse_field, read_blast_file, read_data_lines, split_columns, write_output, HorScanError,
+    MonAlignmentRow,
+};
+use std::io::Write;
+use std::path::Path;
+
+/// 需要映射的 source 区间（BED，半开区间）
+#[derive(Clone, Debug)]
+pub struct Interval {
+    pub chrom: String,
+    pub start: i32,
+    pub end: i32,
+    pub name: String,
+}
+
+/// 区间映射到 target 上的一段
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LiftedPart {
+    pub chrom: String,
+    pub start: i32,
+    pub end: i32,
+    /// 该段中落在 MTH/MIS 单体内的 source 碱基数
+    pub mapped_bases: i32,
+}
+
+/// 读取 BED 区间文件，至少 3 列，第 4 列为名称（可省略）
+pub fn read_intervals<P: AsRef<Path>>(path: P) -> Result<Vec<Interval>, HorScanError> {
+    let name = path.as_ref().display().to_string();
+    let mut intervals = Vec::new();
+    for (line_number, line) in read_data_lines(&path)? {
+        let parts = split_columns(&line, 3, &name, line_number)?;
+        intervals.push(Interval {
+            chrom: parts[0].to_string(),
+            start: parse_field(parts[1], "start", &name, line_number, 2)?,
+            end: parse_field(parts[2], "end", &name, line_number, 3)?,
+            name: parts.get(3).unwrap_or(&".").to_string(),
+        });
+    }
+    Ok(intervals)
+}
+
+/// 通过比对中的 MTH/MIS 行将 source 区间映射到 target
+pub struct Liftover<'a> {
+    rows: &'a [MonAlignmentRow],
+    /// MTH/MIS 行在 rows 中的下标，按 source 坐标排序
+    aligned: Vec<usize>,
+    /// gaps_before[k]：rows[..k] 中 INS/DEL 行的数量
+    gaps_before: Vec<usize>,
+}
+
+impl<'a> Liftover<'a> {
+    pub fn new(rows: &'a [MonAlignmentRow]) -> Liftover<'a> {
+        let mut aligned: Vec<usize> = (0..rows.len())
+            .filter(|&k| matches!(rows[k].align_type.as_str(), "MTH" | "MIS"))
+            .collect();
+        aligned.sort_by_key(|&k| rows[k].source_start);
+        let mut gaps_before = Vec::with_capacity(rows.len() + 1);
+        gaps_before.push(0);
+        for row in rows {
+            let gap = matches!(row.align_type.as_str(), "INS" | "DEL");
+            gaps_before.push(gaps_before.last().unwrap_or(&0) + gap as usize);
+        }
+        Liftover {
+            rows,
+            aligned,
+            gaps_before,
+        }
+    }
+
+    /// 单体内按长度比例插值
+    fn project(row: &MonAlignmentRow, position: i32) -> i32 {
+        let source_len = (row.source_end - row.source_start) as i64;
+        if source_len == 0 {
+            return row.target_start;
+        }
+        let target_len = (row.target_end - row.target_start) as i64;
+        let offset = (position - row.source_start) as i64;
+        row.target_start + ((offset * target_len + source_len / 2) / source_len) as i32
+    }
+
+    /// 映射一个区间；中间隔着 INS/DEL 行的两段分别输出，没有落在 MTH/MIS 单体内的区间返回空
+    pub fn lift(&self, interval: &Interval) -> Vec<LiftedPart> {
+        let first = self
+            .aligned
+            .partition_point(|&k| self.rows[k].source_end <= interval.start);
+        let mut parts: Vec<LiftedPart> = Vec::new();
+        let mut last_row: Option<usize> = None;
+        for &k in &self.aligned[first..] {
+            let row = &self.rows[k];
+            if row.source_start >= interval.end {
+                break;
+            }
+            if row.source != interval.chrom {
+                continue;
+            }
+            let start = interval.start.max(row.source_start);
+            let end = interval.end.min(row.source_end);
+            if start >= end {
+                continue;
+            }
+            let (target_start, target_end) = (Self::project(row, start), Self::project(row, end));
+            let contiguous = match last_row {
+                Some(prev) => self.gaps_before[prev + 1] == self.gaps_before[k] && prev < k,
+                None => false,
+            };
+            match parts.last_mut() {
+                Some(part) if contiguous => {
+                    part.end = part.end.max(target_end);
+                    part.mapped_bases += end - start;
+                }
+                _ => parts.push(LiftedPart {
+                    chrom: row.target.clone(),
+                    start: target_start,
+                    end: target_end,
+                    mapped_bases: end - start,
+                }),
+            }
+            last_row = Some(k);
+        }
+        parts
+    }
+}
+
+/// liftover 命令：将 source 区间映射到 target，写出 `<prefix>.lifted.bed`
+///
+/// 每个区间的每一段一行：source 区间、名称、状态 (mapped/split/unmapped)、target 区间、
+/// 映射碱基比例和段号；未映射的区间 target 列为 `.`
+pub fn run_liftover(
+    alignment_path: &str,
+    intervals_path: &str,
+    output_prefix: &str,
+) -> Result<(), HorScanError> {
+    let rows = read_blast_file(alignment_path)?;
+    let intervals = read_intervals(intervals_path)?;
+    let liftover = Liftover::new(&rows);
+    let (mut mapped, mut split, mut unmapped) = (0, 0, 0);
+
+    write_output(format!("{}.lifted.bed", output_prefix), |writer| {
+        writeln!(
+            writer,
+            "#chrom\tstart\tend\tname\tstatus\ttarget\ttarget_start\ttarget_end\tmapped_fraction\tpart"
+        )?;
+        for interval in &intervals {
+            let parts = liftover.lift(interval);
+            let length = (interval.end - interval.start).max(1) as f64;
+            let source = format!(
+                "{}\t{}\t{}\t{}",
+                interval.chrom, interval.start, interval.end, interval.name
+            );
+            match parts.len() {
+                0 => {
+                    unmapped += 1;
+                    writeln!(writer, "{}\tunmapped\t.\t.\t.\t0.0000\t.", source)?;
+                }
+                n => {
+                    let status = if n == 1 { "mapped" } else { "split" };
+                    if n == 1 {
+                        mapped += 1;
+                    } else {
+                        split += 1;
+                    }
+                    for (idx, part) in parts.iter().enumerate() {
+                        writeln!(
+                            writer,
+                            "{}\t{}\t{}\t{}\t{}\t{:.4}\t{}/{}",
+                            source,
+                            status,
+                            part.chrom,
+                            part.start,
+                            part.end,
+                            part.mapped_bases as f64 / length,
+                            idx + 1,
+                            n
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    })?;
+    log::info!(
+        "liftover: {} intervals, {} mapped, {} split, {} unmapped",
+        intervals.len(),
+        mapped,
+        split,
+        unmapped
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::row_with_length;
+
+    fn interval(start: i32, end: i32) -> Interval {
+        Interval {
+            chrom: "s".to_string(),
+            start,
+            end,
+            name: ".".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_liftover_interpolates_and_splits() {
+        let row = |source, target, kind| row_with_length(100, source, target, kind);
+        let rows = vec![
+            row((0, "A"), (1000, "A"), "MTH"),
+            row((100, "B"), (1100, "C"), "MIS"),
+            row((200, "-"), (1200, "X"), "INS"),
+            row((200, "D"), (1300, "D"), "MTH"),
+            row((300, "E"), (1400, "-"), "DEL"),
+        ];
+        let liftover = Liftover::new(&rows);
+        let part = |start, end, mapped| LiftedPart {
+            chrom: "t".to_string(),
+            start,
+            end,
+            mapped_bases: mapped,
+        };
+        assert_eq!(liftover.lift(&interval(25, 75)), [part(1025, 1075, 50)]);
+        assert_eq!(liftover.lift(&interval(50, 150)), [part(1050, 1150, 100)]);
+        // 跨过 DEL 时拆成两段
+        assert_eq!(
+            liftover.lift(&interval(150, 250)),
+            [part(1150, 1200, 50), part(1300, 1350, 50)]
+        );
+        // 只落在 INS 单体内
+        assert!(liftover.lift(&interval(310, 390)).is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::cell::RefCell;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// 输出到 stderr 的简单日志实现，格式为 `[HH:MM:SS.mmm LEVEL] message`（相对启动时间）
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+thread_local! {
+    /// 开启后本线程的日志先写入缓冲区，由调用方整体输出（batch 的工作线程使用）
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let elapsed = STARTED.get_or_init(Instant::now).elapsed();
+        let secs = elapsed.as_secs();
+        let line = format!(
+            "[{:02}:{:02}:{:02}.{:03} {:5}] {}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            elapsed.subsec_millis(),
+            record.level(),
+            record.args()
+        );
+        let line = CAPTURED.with(|captured| match captured.borrow_mut().as_mut() {
+            Some(lines) => {
+                lines.push(line);
+                None
+            }
+            None => Some(line),
+        });
+        if let Some(line) = line {
+            let _ = writeln!(std::io::stderr().lock(), "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+static PROGRESS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 由 `-v`/`-q` 的次数得到日志级别，默认 info
+pub fn level_from_verbosity(verbose: u8, quiet: u8) -> LevelFilter {
+    match verbose as i16 - quiet as i16 {
+        i16::MIN..=-3 => LevelFilter::Off,
+        -2 => LevelFilter::Error,
+        -1 => LevelFilter::Warn,
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// 安装 stderr 日志，重复调用时只更新级别
+pub fn init(level: LevelFilter) {
+    STARTED.get_or_init(Instant::now);
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
+
+/// 开始缓存本线程的日志
+pub fn start_capture() {
+    CAPTURED.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+}
+
+/// 结束缓存并返回缓存的日志行
+pub fn take_captured() -> Vec<String> {
+    CAPTURED.with(|captured| captured.borrow_mut().take().unwrap_or_default())
+}
+
+/// 将缓存的日志行连续写到 stderr，不与其他线程的输出交错
+pub fn write_captured(lines: &[String]) {
+    let mut stderr = std::io::stderr().lock();
+    for line in lines {
+        let _ = writeln!(stderr, "{}", line);
+    }
+}
+
+/// 多线程运行时关闭进度条，避免输出交错
+pub fn set_progress_enabled(enabled: bool) {
+    PROGRESS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs / 60 % 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+/// 长时间循环的进度显示：终端中原地刷新，非终端时每 10 秒输出一行日志
+pub struct Progress {
+    label: &'static str,
+    total: usize,
+    started: Instant,
+    last_report: Instant,
+    interval: Duration,
+    terminal: bool,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: usize) -> Progress {
+        let terminal = std::io::stderr().is_terminal();
+        let now = Instant::now();
+        Progress {
+            label,
+            total,
+            started: now,
+            last_report: now,
+            interval: if terminal {
+                Duration::from_millis(250)
+            } else {
+                Duration::from_secs(10)
+            },
+            terminal,
+            enabled: PROGRESS_ENABLED.load(Ordering::Relaxed) && log::log_enabled!(Level::Info),
+        }
+    }
+
+    /// done 为已完成的工作量
+    pub fn update(&mut self, done: usize) {
+        if !self.enabled || self.last_report.elapsed() < self.interval {
+            return;
+        }
+        self.last_report = Instant::now();
+        let fraction = done as f64 / self.total.max(1) as f64;
+        let elapsed = self.started.elapsed();
+        let eta = if fraction > 0.0 {
+            format_duration(elapsed.mul_f64((1.0 - fraction) / fraction))
+        } else {
+            "?".to_string()
+        };
+        let message = format!(
+            "{}: {}/{} ({:.1}%) elapsed {} ETA {}",
+            self.label,
+            done,
+            self.total,
+            fraction * 100.0,
+            format_duration(elapsed),
+            eta
+        );
+        if self.terminal {
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K{}", message);
+            let _ = stderr.flush();
+        } else {
+            log::info!("{}", message);
+        }
+    }
+
+    pub fn finish(&mut self) {
+        if self.enabled && self.terminal && self.started != self.last_report {
+            let _ = write!(std::io::stderr().lock(), "\r\x1b[2K");
+        }
+    }
+}
+
+/// 当前进程的常驻内存和峰值常驻内存（KB），仅在 Linux 上可用
+pub fn memory_usage_kb() -> (Option<u64>, Option<u64>) {
+    let status = match std::fs::read_to_string("/proc/self/status") {
+        Ok(status) => status,
+        Err(_) => return (None, None),
+    };
+    let field = |name: &str| {
+        status
+            .lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|value| value.parse().ok())
+    };
+    (field("VmRSS:"), field("VmHWM:"))
+}
+
+/// 一个阶段的耗时与内存记录
+#[derive(Clone, Debug)]
+pub struct StageRecord {
+    pub stage: String,
+    pub seconds: f64,
+    pub rss_kb: Option<u64>,
+    pub peak_rss_kb: Option<u64>,
+}
+
+/// 阶段计时器，finish 时以一行 JSON 记录耗时与内存，便于机器解析
+pub struct StageTimer {
+    stage: &'static str,
+    started: Instant,
+}
+
+impl StageTimer {
+    pub fn start(stage: &'static str) -> StageTimer {
+        log::debug!("stage {} started", stage);
+        StageTimer {
+            stage,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn finish(self) -> StageRecord {
+        let (rss_kb, peak_rss_kb) = memory_usage_kb();
+        let record = StageRecord {
+            stage: self.stage.to_string(),
+            seconds: self.started.elapsed().as_secs_f64(),
+            rss_kb,
+            peak_rss_kb,
+        };
+        log::info!("stage {}", record.to_json());
+        record
+    }
+}
+
+impl StageRecord {
+    /// 单行 JSON；字段都是数字或简单字符串，手工拼接即可
+    pub fn to_json(&self) -> String {
+        let opt = |value: Option<u64>| value.map_or("null".to_string(), |v| v.to_string());
+        format!(
+            "{{\"stage\":\"{}\",\"seconds\":{:.6},\"rss_kb\":{},\"peak_rss_kb\":{}}}",
+            self.stage,
+            self.seconds,
+            opt(self.rss_kb),
+            opt(self.peak_rss_kb)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_from_verbosity() {
+        assert_eq!(level_from_verbosity(0, 0), LevelFilter::Info);
+        assert_eq!(level_from_verbosity(2, 0), LevelFilter::Trace);
+        assert_eq!(level_from_verbosity(0, 1), LevelFilter::Warn);
+        assert_eq!(level_from_verbosity(0, 5), LevelFilter::Off);
+    }
+
+    #[test]
+    fn test_stage_record_json() {
+        let record = StageRecord {
+            stage: "fill".to_string(),
+            seconds: 1.5,
+            rss_kb: Some(10),
+            peak_rss_kb: None,
+        };
+        assert_eq!(
+            record.to_json(),
+            "{\"stage\":\"fill\",\"seconds\":1.500000,\"rss_kb\":10,\"peak_rss_kb\":null}"
+        );
+    }
+}
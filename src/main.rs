@@ -1,37 +1,212 @@
-use io::HorScanError;
+use horscan::batch::{parse_memory_size, run_batch};
+use horscan::horscan::{horscan_main, RunOptions}; // 引入 horscan_main 函数
+use horscan::hor::run_hor;
+use horscan::labels::run_learn_map;
+use horscan::liftover::run_liftover;
+use horscan::phylo::run_all_vs_all;
+use horscan::score::run_score;
+use horscan::significance::PermutationOptions;
+use horscan::simulate::{run_simulate, EventRates, SimulateOptions};
+use horscan::config::resolve_config;
+use horscan::diff::run_diff;
+use horscan::eval::{run_eval, EvalOptions};
+use horscan::window::run_window;
+use horscan::{logging, HorScanError};
 
-use crate::io::Mode;
-use crate::horscan::horscan_main; // 引入 horscan_main 函数
+use std::process::ExitCode;
+
+use crate::args::Command;
 
-mod io;
 mod args;
-mod horscan;
-mod optimize;
 
 
-fn main() -> Result<(), HorScanError> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run() -> Result<(), HorScanError> {
     let params = args::parse_args();
-    let mode;
-    if params.mode.len() < 3 {
-        println!("run with default params");
-        mode = Mode {
-            // match_method: 0,
-            match_score: 4,
-            mismatch_score: -5,
-            gap_score: -2,
-            // jump_max: 10,
-        };
-        println!("{:?}", mode);
-    } else {
-        mode = Mode {
-            // match_method: params.mode[0],
-            match_score: params.mode[0],
-            mismatch_score: params.mode[1] * -1,
-            gap_score: params.mode[2] * -1,
-            // jump_max: params.mode[4] as usize,
-        };
-        println!("{:?}", mode);
+    logging::init(logging::level_from_verbosity(params.verbose, params.quiet));
+    match params.command {
+        Some(Command::Batch(batch_args)) => {
+            let config = resolve_config(
+                batch_args.config.as_deref(),
+                batch_args.preset.as_deref(),
+                &batch_args.mode,
+                batch_args.validate,
+            )?;
+            return run_batch(
+                &batch_args.manifest,
+                batch_args.threads,
+                parse_memory_size(&batch_args.memory)?,
+                &config.mode(),
+                &RunOptions {
+                    validation: config.validation()?,
+                    optimize: config.optimizer.enabled,
+                    ..RunOptions::default()
+                },
+            );
+        }
+        Some(Command::AllVsAll(all_args)) => {
+            let config = resolve_config(
+                all_args.config.as_deref(),
+                all_args.preset.as_deref(),
+                &all_args.mode,
+                None,
+            )?;
+            return run_all_vs_all(
+                all_args.beds,
+                all_args.list,
+                &all_args.output,
+                all_args.threads,
+                &config.mode(),
+                &RunOptions {
+                    optimize: config.optimizer.enabled,
+                    phase: all_args.phase,
+                    ..RunOptions::default()
+                },
+            );
+        }
+        Some(Command::Score(score_args)) => {
+            let config = resolve_config(
+                score_args.config.as_deref(),
+                score_args.preset.as_deref(),
+                &score_args.mode,
+                score_args.validate,
+            )?;
+            return run_score(
+                &score_args.alignment,
+                &score_args.source,
+                &score_args.target,
+                &config.mode(),
+                &RunOptions {
+                    source_region: score_args.source_region,
+                    target_region: score_args.target_region,
+                    validation: config.validation()?,
+                    label_map: score_args.label_map,
+                    ..RunOptions::default()
+                },
+            )
+        }
+        Some(Command::Diff(diff_args)) => {
+            return run_diff(&diff_args.a, &diff_args.b, &diff_args.output)
+        }
+        Some(Command::Liftover(liftover_args)) => {
+            return run_liftover(
+                &liftover_args.alignment,
+                &liftover_args.intervals,
+                &liftover_args.output,
+            )
+        }
+        Some(Command::Hor(hor_args)) => {
+            let config = resolve_config(
+                hor_args.config.as_deref(),
+                hor_args.preset.as_deref(),
+                &hor_args.mode,
+                hor_args.validate,
+            )?;
+            return run_hor(
+                &hor_args.source,
+                hor_args.target.as_deref(),
+                &hor_args.output,
+                hor_args.max_period,
+                &config.mode(),
+                config.validation()?,
+            );
+        }
+        Some(Command::Simulate(sim_args)) => {
+            let options = SimulateOptions {
+                unit: sim_args.unit,
+                copies: sim_args.copies,
+                rates: EventRates {
+                    expansion: sim_args.expansion_rate,
+                    contraction: sim_args.contraction_rate,
+                    substitution: sim_args.substitution_rate,
+                    partial_indel: sim_args.indel_rate,
+                    inversion: sim_args.inversion_rate,
+                },
+                max_copies: sim_args.max_copies,
+                monomer_length: sim_args.monomer_length,
+                source_name: sim_args.source_name,
+                target_name: sim_args.target_name,
+                seed: sim_args.seed,
+            };
+            return run_simulate(&options, &sim_args.output);
+        }
+        Some(Command::Eval(eval_args)) => {
+            return run_eval(
+                &eval_args.truth,
+                eval_args.alignment.as_deref(),
+                eval_args.calls.as_deref(),
+                &eval_args.output,
+                &EvalOptions {
+                    max_distance: eval_args.max_distance,
+                    min_size_similarity: eval_args.size_similarity,
+                },
+            )
+        }
+        Some(Command::Window(window_args)) => {
+            return run_window(
+                &window_args.alignment,
+                window_args.window,
+                window_args.step,
+                &window_args.output,
+            )
+        }
+        Some(Command::LearnMap(map_args)) => {
+            return run_learn_map(
+                &map_args.source,
+                &map_args.target,
+                &map_args.output,
+                map_args.validate,
+            )
+        }
+        None => {}
     }
-    horscan_main(params.source, params.target, params.output, mode)?;
+    // 配置优先级：预设 < 配置文件 < 命令行参数
+    let config = resolve_config(
+        params.config.as_deref(),
+        params.preset.as_deref(),
+        &params.mode,
+        params.validate,
+    )?;
+    log::info!("effective config:\n{}", config.to_toml()?);
+
+    // clap 保证没有子命令时 source/target/output 均已给出
+    let output = params.output.unwrap_or_default();
+    config.save(format!("{}.config.toml", output))?;
+    let dump_matrix = params
+        .dump_matrix
+        .then(|| format!("{}.score.npy", output));
+    horscan_main(
+        params.source.unwrap_or_default(),
+        params.target.unwrap_or_default(),
+        output,
+        config.mode(),
+        RunOptions {
+            source_region: params.source_region,
+            target_region: params.target_region,
+            validation: config.validation()?,
+            optimize: config.optimizer.enabled,
+            matrix_backend: params.matrix,
+            dump_matrix,
+            dump_max_size: params.dump_max_size,
+            temp_prefix: params.prefix,
+            label_map: params.label_map,
+            phase: params.phase,
+            free_gaps: None,
+            permutation: (params.permutations > 0).then_some(PermutationOptions {
+                permutations: params.permutations,
+                shuffle: params.shuffle,
+                seed: params.seed,
+            }),
+        },
+    )?;
     Ok(())
-}
\ No newline at end of file
+}
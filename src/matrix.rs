@@ -0,0 +1,227 @@
+use crate::io::HorScanError;
+use memmap2::MmapMut;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::str::FromStr;
+
+/// 分块边长（单元数），一个分块为 256 x 256 个 i32，即 256 KiB
+pub const TILE: usize = 256;
+const TILE_SHIFT: u32 = TILE.trailing_zeros();
+const TILE_CELLS: usize = TILE * TILE;
+
+/// 得分矩阵的存储方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatrixBackend {
+    /// 保存在内存中
+    #[default]
+    Memory,
+    /// 保存在内存映射文件中，由操作系统按需换入换出
+    Mmap,
+}
+
+impl FromStr for MatrixBackend {
+    type Err = HorScanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(MatrixBackend::Memory),
+            "mmap" => Ok(MatrixBackend::Mmap),
+            _ => Err(HorScanError::InvalidValue(format!(
+                "unknown matrix backend '{}', expected memory or mmap",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for MatrixBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MatrixBackend::Memory => "memory",
+            MatrixBackend::Mmap => "mmap",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+enum Storage {
+    Memory(Vec<i32>),
+    Mmap { map: MmapMut, path: String },
+}
+
+/// 按 TILE x TILE 分块存储的得分矩阵
+///
+/// 分块内按行存放，分块之间也按行排列。按分块填充和沿对角线回溯时，
+/// 访问都集中在少数几个分块内，对内存映射文件来说意味着很少的缺页。
+pub struct ScoreMatrix {
+    rows: usize,
+    cols: usize,
+    tiles_per_row: usize,
+    storage: Storage,
+}
+
+/// rows x cols 的矩阵补齐到整数个分块后的单元数
+fn padded_cells(rows: usize, cols: usize) -> usize {
+    rows.div_ceil(TILE) * cols.div_ceil(TILE) * TILE_CELLS
+}
+
+impl ScoreMatrix {
+    /// 预估 rows x cols 的矩阵常驻内存的字节数。Memory 后端为补齐到整数个分块后的整个矩阵；
+    /// Mmap 后端只计入填充时同时访问的两个行带，其余页面由操作系统换出
+    pub fn bytes_for(rows: usize, cols: usize, backend: MatrixBackend) -> u64 {
+        let cells = match backend {
+            MatrixBackend::Memory => padded_cells(rows, cols),
+            MatrixBackend::Mmap => padded_cells(rows.min(2 * TILE), cols),
+        };
+        (cells * std::mem::size_of::<i32>()) as u64
+    }
+
+    /// 分配 rows x cols 的矩阵；Mmap 后端的文件写在 path，用完后删除
+    pub fn new(
+        rows: usize,
+        cols: usize,
+        backend: MatrixBackend,
+        path: &str,
+    ) -> Result<ScoreMatrix, HorScanError> {
+        let tiles_per_row = cols.div_ceil(TILE);
+        let cells = padded_cells(rows, cols);
+        let storage = match backend {
+            MatrixBackend::Memory => Storage::Memory(vec![0; cells]),
+            MatrixBackend::Mmap => {
+                let write_error = |source| HorScanError::Write {
+                    path: path.to_string(),
+                    source,
+                };
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)
+                    .map_err(write_error)?;
+                file.set_len((cells * 4) as u64).map_err(write_error)?;
+                // 文件只由本进程使用，运行期间不会被其他进程修改
+                let map = unsafe { MmapMut::map_mut(&file) }.map_err(write_error)?;
+                log::info!(
+                    "score matrix {} x {} mapped to {} ({} MiB)",
+                    rows,
+                    cols,
+                    path,
+                    (cells * 4) >> 20
+                );
+                Storage::Mmap {
+                    map,
+                    path: path.to_string(),
+                }
+            }
+        };
+        Ok(ScoreMatrix {
+            rows,
+            cols,
+            tiles_per_row,
+            storage,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    #[inline]
+    fn offset(&self, i: usize, j: usize) -> usize {
+        let tile = (i >> TILE_SHIFT) * self.tiles_per_row + (j >> TILE_SHIFT);
+        (tile << (2 * TILE_SHIFT)) + ((i & (TILE - 1)) << TILE_SHIFT) + (j & (TILE - 1))
+    }
+
+    fn cells(&self) -> &[i32] {
+        match &self.storage {
+            Storage::Memory(cells) => cells,
+            // mmap 按页对齐，长度为 4 的倍数
+            Storage::Mmap { map, .. } => unsafe {
+                std::slice::from_raw_parts(map.as_ptr() as *const i32, map.len() / 4)
+            },
+        }
+    }
+
+    fn cells_mut(&mut self) -> &mut [i32] {
+        match &mut self.storage {
+            Storage::Memory(cells) => cells,
+            Storage::Mmap { map, .. } => unsafe {
+                std::slice::from_raw_parts_mut(map.as_mut_ptr() as *mut i32, map.len() / 4)
+            },
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, i: usize, j: usize) -> i32 {
+        self.cells()[self.offset(i, j)]
+    }
+
+    #[inline]
+    pub fn set(&mut self, i: usize, j: usize, value: i32) {
+        let offset = self.offset(i, j);
+        self.cells_mut()[offset] = value;
+    }
+
+    /// 复制出第 i 行
+    pub fn row(&self, i: usize) -> Vec<i32> {
+        (0..self.cols).map(|j| self.get(i, j)).collect()
+    }
+
+    pub fn set_row(&mut self, i: usize, values: &[i32]) {
+        for (j, &value) in values.iter().enumerate().take(self.cols) {
+            self.set(i, j, value);
+        }
+    }
+}
+
+impl Drop for ScoreMatrix {
+    fn drop(&mut self) {
+        if let Storage::Mmap { path, .. } = &self.storage {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+
+    #[test]
+    fn test_tiled_matrix_backends_agree() -> Result<(), HorScanError> {
+        let dir = TestDir::new("matrix");
+        let path = dir.path("matrix.mmap").display().to_string();
+        let (rows, cols) = (TILE + 3, 2 * TILE + 1);
+        let mut memory = ScoreMatrix::new(rows, cols, MatrixBackend::Memory, &path)?;
+        let mut mapped = ScoreMatrix::new(rows, cols, MatrixBackend::Mmap, &path)?;
+        for i in 0..rows {
+            for j in 0..cols {
+                let value = (i * cols + j) as i32;
+                memory.set(i, j, value);
+                mapped.set(i, j, value);
+            }
+        }
+        assert_eq!(memory.get(TILE, TILE + 1), (TILE * cols + TILE + 1) as i32);
+        for i in [0, TILE - 1, TILE, rows - 1] {
+            assert_eq!(memory.row(i), mapped.row(i));
+        }
+        drop(mapped);
+        assert!(!std::path::Path::new(&path).exists());
+
+        // 预估按补齐后的分块计算：2 x 3 个分块
+        let bytes = ScoreMatrix::bytes_for(rows, cols, MatrixBackend::Memory);
+        assert_eq!(bytes, (memory.cells().len() * 4) as u64);
+        assert_eq!(bytes, (2 * 3 * TILE_CELLS * 4) as u64);
+        let big = 100 * TILE;
+        assert_eq!(
+            ScoreMatrix::bytes_for(big, big, MatrixBackend::Mmap),
+            (2 * 100 * TILE_CELLS * 4) as u64
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::io::{write_output, HorScanError};
+use crate::matrix::ScoreMatrix;
+use std::io::Write;
+
+/// 写出 NumPy `.npy`（格式版本 1.0）的文件头，数据为小端 i32、C 顺序
+fn npy_header(rows: usize, cols: usize) -> Vec<u8> {
+    let mut dict = format!(
+        "{{'descr': '<i4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        rows, cols
+    );
+    // magic(6) + 版本(2) + 头长度(2) + 字典，总长补齐到 64 字节的倍数，以换行结尾
+    let unpadded = 10 + dict.len() + 1;
+    dict.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    dict.push('\n');
+
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+/// 将得分矩阵写为 `.npy`；给出 max_size 时按块取最大值降采样，使每一维不超过 max_size
+///
+/// 逐行读取矩阵，写出时不需要在内存中保留整个矩阵
+pub fn save_score_matrix_npy(
+    matrix: &ScoreMatrix,
+    path: &str,
+    max_size: Option<usize>,
+) -> Result<(), HorScanError> {
+    let (rows, cols) = (matrix.rows(), matrix.cols());
+    let limit = max_size.unwrap_or(usize::MAX).max(1);
+    let row_step = rows.div_ceil(limit);
+    let col_step = cols.div_ceil(limit);
+    let out_rows = rows.div_ceil(row_step);
+    let out_cols = cols.div_ceil(col_step);
+    if row_step > 1 || col_step > 1 {
+        log::info!(
+            "score matrix {} x {} downsampled to {} x {} (max of {} x {} blocks)",
+            rows,
+            cols,
+            out_rows,
+            out_cols,
+            row_step,
+            col_step
+        );
+    }
+
+    write_output(path, |writer| {
+        writer.write_all(&npy_header(out_rows, out_cols))?;
+        let mut pooled = vec![i32::MIN; out_cols];
+        for i in 0..rows {
+            for (j, value) in matrix.row(i).into_iter().enumerate() {
+                let cell = &mut pooled[j / col_step];
+                *cell = (*cell).max(value);
+            }
+            if (i + 1) % row_step == 0 || i + 1 == rows {
+                for value in pooled.iter_mut() {
+                    writer.write_all(&value.to_le_bytes())?;
+                    *value = i32::MIN;
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestDir;
+    use crate::matrix::MatrixBackend;
+
+    #[test]
+    fn test_save_npy_downsampled() -> Result<(), HorScanError> {
+        let dir = TestDir::new("npy");
+        let mut matrix = ScoreMatrix::new(5, 4, MatrixBackend::Memory, "")?;
+        for i in 0..5 {
+            for j in 0..4 {
+                matrix.set(i, j, (i * 4 + j) as i32);
+            }
+        }
+        let full = dir.path("full.npy").display().to_string();
+        save_score_matrix_npy(&matrix, &full, None)?;
+        let bytes = std::fs::read(&full)?;
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!(&bytes[..6], b"\x93NUMPY");
+        assert_eq!((10 + header_len) % 64, 0);
+        assert!(String::from_utf8_lossy(&bytes[10..10 + header_len]).contains("'shape': (5, 4)"));
+        assert_eq!(bytes.len(), 10 + header_len + 5 * 4 * 4);
+
+        // 5 x 4 降采样到最多 2 x 2：行块 3、列块 2，取块内最大值
+        let small = dir.path("small.npy").display().to_string();
+        save_score_matrix_npy(&matrix, &small, Some(2))?;
+        let bytes = std::fs::read(&small)?;
+        let data: Vec<i32> = bytes[10 + header_len..]
+            .chunks_exact(4)
+            .map(|c| i32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        assert_eq!(data, [9, 11, 17, 19]);
+        Ok(())
+    }
+}
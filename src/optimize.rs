@@ -1,19 +1,54 @@
-use crate::io::{read_blast_file, save_blast_file, HorScanError, MonAlignmentRow};
+use crate::horscan::{alignment_score, row_score};
+use crate::io::{HorScanError, Mode, MonAlignmentRow, MonRow};
+
+/// 扫描轮数上限；每次移动都严格提高得分，正常情况下远早于此收敛
+const MAX_PASSES: usize = 100;
+
+/// 块移动时一段 gap 最多向前或向后平移的行数。HOR 扩增造成的 gap 通常被拆在相隔一个 HOR 拷贝的位置，
+/// 40 行足以覆盖常见的 HOR 长度；每段 gap 的试探次数与它成正比
+const MAX_BLOCK_SHIFT: usize = 40;
+
 #[derive(Clone, Debug)]
-struct TypeCount {
+pub struct TypeCount {
     pub mth: i32,
     pub ins: i32,
     pub del: i32,
     pub mis: i32,
 }
 
+impl TypeCount {
+    /// 统计比对行中各 align_type 的数量，未知类型忽略
+    pub fn from_rows(rows: &[MonAlignmentRow]) -> TypeCount {
+        let mut count = TypeCount {
+            mth: 0,
+            ins: 0,
+            del: 0,
+            mis: 0,
+        };
+        for row in rows {
+            match row.align_type.as_str() {
+                "MTH" => count.mth += 1,
+                "INS" => count.ins += 1,
+                "DEL" => count.del += 1,
+                "MIS" => count.mis += 1,
+                _ => {} // 忽略未知类型
+            }
+        }
+        count
+    }
+
+    pub fn total(&self) -> i32 {
+        self.mth + self.ins + self.del + self.mis
+    }
+}
+
 fn find_useful_target_match(
-    blast: &Vec<MonAlignmentRow>,
+    blast: &[MonAlignmentRow],
     current_index: usize,
 ) -> Result<Vec<usize>, HorScanError> {
     // 初始化一个空的Vec来存储有用的ID
     let mut useful_id = vec![];
-    for i in current_index..blast.len() {
+    for i in current_index + 1..blast.len() {
         // 确保前方没有占用的mon
         if blast[i].source_mon != "-" {
             break;
@@ -39,12 +74,12 @@ fn find_useful_target_match(
 }
 
 fn find_useful_source_match(
-    blast: &Vec<MonAlignmentRow>,
+    blast: &[MonAlignmentRow],
     current_index: usize,
 ) -> Result<Vec<usize>, HorScanError> {
     // 初始化一个空的Vec来存储有用的ID
     let mut useful_id = vec![];
-    for i in current_index..blast.len() {
+    for i in current_index + 1..blast.len() {
         // 确保前方没有占用的mon
         if blast[i].target_mon != "-" {
             break;
@@ -69,8 +104,20 @@ fn find_useful_source_match(
     Ok(useful_id)
 }
 
+/// 按 README 的定义由两侧单体确定比对类型：source 为空位是 INS，target 为空位是 DEL，
+/// 两侧都是空位的占位行为 UNX
+pub fn row_type(row: &MonAlignmentRow) -> &'static str {
+    match (row.source_mon == "-", row.target_mon == "-") {
+        (true, true) => "UNX",
+        (true, false) => "INS",
+        (false, true) => "DEL",
+        (false, false) if row.source_mon == row.target_mon => "MTH",
+        (false, false) => "MIS",
+    }
+}
+
 fn swap_blast_line_source(
-    blast: &mut Vec<MonAlignmentRow>,
+    blast: &mut [MonAlignmentRow],
     i: usize,
     j: usize,
 ) -> Result<(), HorScanError> {
@@ -97,26 +144,13 @@ fn swap_blast_line_source(
     blast[i].source_start = temp_row.source_start;
     blast[i].source_end = temp_row.source_end;
     blast[i].source_mon = temp_row.source_mon;
-    // 如果原来i行的target的mon还存在，则进行标记为DEL， 否则为两个 - - 标记为UNX
-    // 根据交换后 blast[i] 的新状态，重新判断其类型
-    if &blast[i].source_mon != "-" && &blast[i].target_mon != "-" {
-        // Source 和 Target 端都存在单体，这是一个 Mismatch
-        blast[i].align_type = "MIS".to_string();
-    } else if &blast[i].source_mon != "-" && &blast[i].target_mon == "-" {
-        // 只有 Source 端有单体，这是一个 Deletion
-        blast[i].align_type = "INS".to_string();
-    } else if &blast[i].source_mon == "-" && &blast[i].target_mon != "-" {
-        // 只有 Target 端有单体，这是一个 Insertion
-        blast[i].align_type = "DEL".to_string();
-    } else {
-        // Source 和 Target 端都是 "-", 这是一个无用的行
-        blast[i].align_type = "UNX".to_string();
-    }
+    // 根据交换后 blast[i] 的新状态，重新判断其类型；两侧均为 - 时标记为UNX，由 compact_alignment 去除
+    blast[i].align_type = row_type(&blast[i]).to_string();
     Ok(())
 }
 
 fn swap_blast_line_target(
-    blast: &mut Vec<MonAlignmentRow>,
+    blast: &mut [MonAlignmentRow],
     i: usize,
     j: usize,
 ) -> Result<(), HorScanError> {
@@ -144,86 +178,434 @@ fn swap_blast_line_target(
     blast[i].target_start = temp_row.target_start;
     blast[i].target_end = temp_row.target_end;
     blast[i].target_mon = temp_row.target_mon.clone();
-    // 如果原来i行的target的mon还存在，则进行标记为DEL， 否则为两个 - - 标记为UNX
-    // 根据交换后 blast[i] 的新状态，重新判断其类型
-    if &blast[i].source_mon != "-" && &blast[i].target_mon != "-" {
-        // Source 和 Target 端都存在单体，这是一个 Mismatch
-        blast[i].align_type = "MIS".to_string();
-    } else if &blast[i].source_mon != "-" && &blast[i].target_mon == "-" {
-        // 只有 Source 端有单体，这是一个 Deletion
-        blast[i].align_type = "INS".to_string();
-    } else if &blast[i].source_mon == "-" && &blast[i].target_mon != "-" {
-        // 只有 Target 端有单体，这是一个 Insertion
-        blast[i].align_type = "DEL".to_string();
-    } else {
-        // Source 和 Target 端都是 "-", 这是一个无用的行
-        blast[i].align_type = "UNX".to_string();
-    }
+    // 根据交换后 blast[i] 的新状态，重新判断其类型；两侧均为 - 时标记为UNX，由 compact_alignment 去除
+    blast[i].align_type = row_type(&blast[i]).to_string();
     Ok(())
 }
 
-// optimize blast file to improve global score
-pub fn optimize_blast_file(optimize_path: String) -> Result<(), HorScanError> {
-    // 读取blast文件
-    let mut blast = read_blast_file(optimize_path.clone())?;
+/// 一次优化的结果
+#[derive(Clone, Debug)]
+pub struct OptimizeReport {
+    pub score_before: i32,
+    pub score_after: i32,
+    /// 接受的移动数（含块移动）
+    pub moves: usize,
+    /// 其中整段 gap 平移的块移动数
+    pub block_moves: usize,
+    /// 扫描轮数
+    pub passes: usize,
+    /// 最后一轮没有任何移动；为 false 时是达到 MAX_PASSES 后停止的
+    pub converged: bool,
+}
+
+type Swap = fn(&mut [MonAlignmentRow], usize, usize) -> Result<(), HorScanError>;
 
-    // 第一遍变量 统计 align_type 的类别数量
-    let mut type_count = TypeCount {
-        mth: 0,
-        ins: 0,
-        del: 0,
-        mis: 0,
-    }; // 初始化结构体
-    for row in &blast {
-        match row.align_type.as_str() {
-            "MTH" => type_count.mth += 1,
-            "INS" => type_count.ins += 1,
-            "DEL" => type_count.del += 1,
-            "MIS" => type_count.mis += 1,
-            _ => {} // 忽略未知类型
+/// 试探一次移动：只改变 i、j 两行，两行得分之和提高时保留，否则还原
+fn try_move(
+    blast: &mut [MonAlignmentRow],
+    i: usize,
+    j: usize,
+    mode: &Mode,
+    swap: Swap,
+) -> Result<bool, HorScanError> {
+    let (old_i, old_j) = (blast[i].clone(), blast[j].clone());
+    let before = row_score(&old_i, mode) + row_score(&old_j, mode);
+    swap(blast, i, j)?;
+    if row_score(&blast[i], mode) + row_score(&blast[j], mode) > before {
+        return Ok(true);
+    }
+    blast[i] = old_i;
+    blast[j] = old_j;
+    Ok(false)
+}
+
+/// 在内存中优化比对行：将非 MTH 行的单体移到相邻 gap 区中相同标签的位置，
+/// 只接受提高 Mode 下得分的移动；之后整段平移 gap（见 block_moves），反复扫描直到一轮中没有任何移动
+pub fn optimize_alignment(
+    blast: &mut [MonAlignmentRow],
+    mode: &Mode,
+) -> Result<OptimizeReport, HorScanError> {
+    let score_before = alignment_score(blast, mode);
+    let mut moves = 0;
+    let mut block_moves_total = 0;
+    let mut passes = 0;
+    let mut converged = false;
+    while passes < MAX_PASSES {
+        passes += 1;
+        let mut moved = 0;
+        for i in 0..blast.len() {
+            if blast[i].align_type == "MTH" {
+                continue;
+            }
+            // 每一行source 和 target 都能找到匹配并替换，但是不会影响到序列顺序
+            if let Some(&j) = find_useful_target_match(blast, i)?.first() {
+                moved += try_move(blast, i, j, mode, swap_blast_line_source)? as usize;
+            }
+            if let Some(&j) = find_useful_source_match(blast, i)?.first() {
+                moved += try_move(blast, i, j, mode, swap_blast_line_target)? as usize;
+            }
         }
+        // 单行移动之后再尝试整段平移 gap
+        let blocks =
+            block_moves(blast, mode, Side::Source) + block_moves(blast, mode, Side::Target);
+        block_moves_total += blocks;
+        moved += blocks;
+        moves += moved;
+        if moved == 0 {
+            converged = true;
+            break;
+        }
+    }
+    if !converged {
+        log::warn!(
+            "optimize: stopped after {} passes without converging, the alignment may still improve",
+            MAX_PASSES
+        );
     }
-    println!("Default Alignment: {:?}", type_count);
+    Ok(OptimizeReport {
+        score_before,
+        score_after: alignment_score(blast, mode),
+        moves,
+        block_moves: block_moves_total,
+        passes,
+        converged,
+    })
+}
 
-    for i in 0..blast.len() {
-        if blast[i].align_type == "MTH" {
-            continue;
+/// 比对的一侧
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Source,
+    Target,
+}
+
+fn side_mon(row: &MonAlignmentRow, side: Side) -> &str {
+    match side {
+        Side::Source => &row.source_mon,
+        Side::Target => &row.target_mon,
+    }
+}
+
+/// 该侧为空位而另一侧有单体的行
+fn is_gap(row: &MonAlignmentRow, side: Side) -> bool {
+    let other = match side {
+        Side::Source => Side::Target,
+        Side::Target => Side::Source,
+    };
+    side_mon(row, side) == "-" && side_mon(row, other) != "-"
+}
+
+/// 交换 i、j 两行在 side 一侧的单体（样本、坐标与标签）
+fn swap_side(blast: &mut [MonAlignmentRow], side: Side, i: usize, j: usize) {
+    if i == j {
+        return;
+    }
+    let (low, high) = (i.min(j), i.max(j));
+    let (head, tail) = blast.split_at_mut(high);
+    let (a, b) = (&mut head[low], &mut tail[0]);
+    match side {
+        Side::Source => {
+            std::mem::swap(&mut a.source, &mut b.source);
+            std::mem::swap(&mut a.source_start, &mut b.source_start);
+            std::mem::swap(&mut a.source_end, &mut b.source_end);
+            std::mem::swap(&mut a.source_mon, &mut b.source_mon);
+        }
+        Side::Target => {
+            std::mem::swap(&mut a.target, &mut b.target);
+            std::mem::swap(&mut a.target_start, &mut b.target_start);
+            std::mem::swap(&mut a.target_end, &mut b.target_end);
+            std::mem::swap(&mut a.target_mon, &mut b.target_mon);
+        }
+    }
+}
+
+/// side 一侧 [start, end) 行的单体倒序
+fn reverse_side(blast: &mut [MonAlignmentRow], side: Side, start: usize, end: usize) {
+    let (mut i, mut j) = (start, end);
+    while i + 1 < j {
+        swap_side(blast, side, i, j - 1);
+        i += 1;
+        j -= 1;
+    }
+}
+
+/// 窗口内各行按两侧标签计算的得分之和与事件数；事件的划分与 sv::call_events 相同
+fn window_cost(pairs: &[(&str, &str)], mode: &Mode) -> (i32, usize) {
+    let mut score = 0;
+    let mut events = 0;
+    let mut open: Option<&str> = None;
+    for &(source, target) in pairs {
+        let kind = match (source == "-", target == "-") {
+            (true, true) => continue,
+            (true, false) => "INS",
+            (false, true) => "DEL",
+            (false, false) if source == target => "MTH",
+            (false, false) => "MIS",
+        };
+        score += match kind {
+            "MTH" => mode.match_score,
+            "MIS" => mode.mismatch_score,
+            _ => mode.gap_score,
+        };
+        if kind == "MTH" {
+            open = None;
+        } else if open != Some(kind) {
+            events += 1;
+            open = Some(kind);
         }
-        // 每一行source 和 target 都能找到匹配并替换，但是不会影响到序列顺序
-        let source_swap = find_useful_target_match(&blast, i)?;
-        let target_swap = find_useful_source_match(&blast, i)?;
+    }
+    (score, events)
+}
 
-        if source_swap.len() > 0 {
-            let j = source_swap[0];
-            let _ = swap_blast_line_source(&mut blast, i, j);
+/// 整段平移 side 一侧的 gap：对每段连续的 gap 行，尝试向前或向后平移 1..=MAX_BLOCK_SHIFT 行
+/// （被越过的单体移到 gap 的另一端，两侧顺序都不变），取得分最高、其次事件数最少的位置；
+/// 只有得分提高，或得分不变而事件数减少时才移动。返回移动的段数
+fn block_moves(blast: &mut [MonAlignmentRow], mode: &Mode, side: Side) -> usize {
+    let mut moved = 0;
+    let mut start = 0;
+    while start < blast.len() {
+        if !is_gap(&blast[start], side) {
+            start += 1;
+            continue;
         }
-        if target_swap.len() > 0 {
-            let j = target_swap[0];
-            let _ = swap_blast_line_target(&mut blast, i, j);
+        let mut end = start + 1;
+        while end < blast.len() && is_gap(&blast[end], side) {
+            end += 1;
+        }
+        match best_block_shift(blast, mode, side, start, end) {
+            Some((low, mid, high)) => {
+                reverse_side(blast, side, low, mid);
+                reverse_side(blast, side, mid, high);
+                reverse_side(blast, side, low, high);
+                settle_window(blast, side, low, high);
+                moved += 1;
+                start = high;
+            }
+            None => start = end,
         }
     }
+    moved
+}
 
-    // 去除UNX行
-    // blast.retain(|line| line.align_type != "UNX");
-    // 统计optimize 后的结果
-    let mut optimize_result = TypeCount {
-        mth: 0,
-        ins: 0,
-        del: 0,
-        mis: 0,
+/// 一次块移动：交换 [low, mid) 与 [mid, high) 两部分在一侧的单体
+type BlockShift = (usize, usize, usize);
+
+/// 为 [start, end) 段 gap 找到最好的平移
+fn best_block_shift(
+    blast: &[MonAlignmentRow],
+    mode: &Mode,
+    side: Side,
+    start: usize,
+    end: usize,
+) -> Option<BlockShift> {
+    let mut candidates = Vec::new();
+    for shift in 1..=MAX_BLOCK_SHIFT {
+        if shift <= start {
+            // 向前：[start - shift, start) 的单体移到 gap 之后
+            candidates.push((start - shift, start, end));
+        }
+        if end + shift <= blast.len() {
+            // 向后：[end, end + shift) 的单体移到 gap 之前
+            candidates.push((start, end, end + shift));
+        }
+    }
+    let pairs = |low: usize, high: usize, moved: Option<usize>| -> Vec<(&str, &str)> {
+        // 窗口两端各多取一行，以计入与相邻事件的合并
+        let (from, to) = (low.saturating_sub(1), (high + 1).min(blast.len()));
+        let mut labels: Vec<&str> = (from..to).map(|k| side_mon(&blast[k], side)).collect();
+        if let Some(mid) = moved {
+            labels[low - from..high - from].rotate_left(mid - low);
+        }
+        (from..to)
+            .zip(labels)
+            .map(|(k, label)| match side {
+                Side::Source => (label, blast[k].target_mon.as_str()),
+                Side::Target => (blast[k].source_mon.as_str(), label),
+            })
+            .collect()
     };
-    for line in &blast {
-        match line.align_type.as_str() {
-            "MTH" => optimize_result.mth += 1,
-            "INS" => optimize_result.ins += 1,
-            "DEL" => optimize_result.del += 1,
-            "MIS" => optimize_result.mis += 1,
-            _ => {}
+    let mut best: Option<((i32, i64), BlockShift)> = None;
+    for (low, mid, high) in candidates {
+        let (before, before_events) = window_cost(&pairs(low, high, None), mode);
+        let (after, after_events) = window_cost(&pairs(low, high, Some(mid)), mode);
+        let gain = (after - before, before_events as i64 - after_events as i64);
+        if gain > (0, 0) && best.is_none_or(|(best_gain, _)| gain > best_gain) {
+            best = Some((gain, (low, mid, high)));
         }
     }
-    println!("Optimize Alignment: {:?}", optimize_result); // 打印optimize 后的结果
+    best.map(|(_, shift)| shift)
+}
 
-    // 保存修改好的blast文件
-    save_blast_file(&blast, optimize_path.clone())?;
-    Ok(())
+/// 块移动后重新确定 [start, end) 行的类型，并把 side 一侧空位的坐标设为其前一个单体的起点
+fn settle_window(blast: &mut [MonAlignmentRow], side: Side, start: usize, end: usize) {
+    let mut previous = (0..start)
+        .rev()
+        .find(|&k| side_mon(&blast[k], side) != "-")
+        .map(|k| match side {
+            Side::Source => blast[k].source_start,
+            Side::Target => blast[k].target_start,
+        });
+    for row in &mut blast[start..end] {
+        if side_mon(row, side) != "-" {
+            previous = Some(match side {
+                Side::Source => row.source_start,
+                Side::Target => row.target_start,
+            });
+        } else if let Some(position) = previous {
+            match side {
+                Side::Source => (row.source_start, row.source_end) = (position, position),
+                Side::Target => (row.target_start, row.target_end) = (position, position),
+            }
+        }
+        row.align_type = row_type(row).to_string();
+    }
+}
+
+/// 优化后的整理：去除两侧都是空位的行，并按 README 的定义重新确定类型，返回去除的行数
+pub fn compact_alignment(blast: &mut Vec<MonAlignmentRow>) -> usize {
+    let before = blast.len();
+    blast.retain(|line| line.source_mon != "-" || line.target_mon != "-");
+    for line in blast.iter_mut() {
+        line.align_type = row_type(line).to_string();
+    }
+    before - blast.len()
+}
+
+/// 检查比对两侧的单体依次恰好是输入的单体：优化只能在列之间移动单体，
+/// 不能丢失、重复或打乱任何一侧的顺序，违反时返回 AlignmentError
+pub fn check_monomer_order(
+    blast: &[MonAlignmentRow],
+    source: &[MonRow],
+    target: &[MonRow],
+) -> Result<(), HorScanError> {
+    let check = |side: &str, aligned: Vec<(i32, i32, &str)>, input: &[MonRow]| {
+        let expected: Vec<(i32, i32, &str)> = input
+            .iter()
+            .map(|row| (row.start, row.end, row.mon.as_str()))
+            .collect();
+        if let Some(k) = aligned.iter().zip(&expected).position(|(a, e)| a != e) {
+            let (start, end, mon) = aligned[k];
+            return Err(HorScanError::AlignmentError(format!(
+                "{} monomer order not preserved: {} ({}-{}) at position {}",
+                side, mon, start, end, k
+            )));
+        }
+        if aligned.len() != expected.len() {
+            return Err(HorScanError::AlignmentError(format!(
+                "{} has {} monomers in the alignment but {} in the input",
+                side,
+                aligned.len(),
+                expected.len()
+            )));
+        }
+        Ok(())
+    };
+    check(
+        "source",
+        blast
+            .iter()
+            .filter(|row| row.source_mon != "-")
+            .map(|row| (row.source_start, row.source_end, row.source_mon.as_str()))
+            .collect(),
+        source,
+    )?;
+    check(
+        "target",
+        blast
+            .iter()
+            .filter(|row| row.target_mon != "-")
+            .map(|row| (row.target_start, row.target_end, row.target_mon.as_str()))
+            .collect(),
+        target,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{row, MONOMER_LENGTH};
+
+    #[test]
+    fn test_optimize_only_improves_score() -> Result<(), HorScanError> {
+        let mode = Mode::from_params(&[10, 4, 2]);
+        // 错配 A-B 之后 gap 区中有 target A：移动后得到 MTH，得分提高
+        let mut rows = vec![
+            row((0, "A"), (0, "B"), "MIS"),
+            row((0, "-"), (0, "C"), "INS"),
+            row((0, "-"), (0, "A"), "INS"),
+            row((0, "D"), (0, "D"), "MTH"),
+        ];
+        let report = optimize_alignment(&mut rows, &mode)?;
+        assert_eq!(report.moves, 1);
+        assert!(report.score_after > report.score_before);
+        assert_eq!(report.score_after, alignment_score(&rows, &mode));
+        assert_eq!(
+            (rows[2].source_mon.as_str(), rows[2].align_type.as_str()),
+            ("A", "MTH")
+        );
+
+        // 已经收敛的比对再次优化不会改变
+        let again = optimize_alignment(&mut rows, &mode)?;
+        assert_eq!((again.moves, again.passes, again.converged), (0, 1, true));
+        assert_eq!(again.score_before, report.score_after);
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_moves() {
+        let mode = Mode::from_params(&[10, 4, 2]);
+        let types = |rows: &[MonAlignmentRow]| -> Vec<String> {
+            rows.iter().map(|r| r.align_type.clone()).collect()
+        };
+        // target 一侧的两行 gap 错位了三行：整段平移后得到三个 MTH 和一段 DEL
+        let mut rows = vec![
+            row((0, "A"), (0, "-"), "DEL"),
+            row((0, "B"), (0, "-"), "DEL"),
+            row((0, "C"), (0, "A"), "MIS"),
+            row((0, "A"), (0, "B"), "MIS"),
+            row((0, "B"), (0, "C"), "MIS"),
+            row((0, "C"), (0, "-"), "DEL"),
+        ];
+        assert_eq!(block_moves(&mut rows, &mode, Side::Target), 1);
+        assert_eq!(types(&rows), ["MTH", "MTH", "MTH", "DEL", "DEL", "DEL"]);
+
+        // 得分相同时合并为更少的事件
+        let mut rows = vec![
+            row((0, "-"), (0, "A"), "INS"),
+            row((0, "A"), (0, "A"), "MTH"),
+            row((0, "-"), (0, "A"), "INS"),
+            row((0, "A"), (0, "A"), "MTH"),
+        ];
+        let before = alignment_score(&rows, &mode);
+        assert_eq!(block_moves(&mut rows, &mode, Side::Source), 1);
+        assert_eq!(alignment_score(&rows, &mode), before);
+        assert_eq!(types(&rows), ["MTH", "INS", "INS", "MTH"]);
+        assert_eq!(block_moves(&mut rows, &mode, Side::Source), 0);
+    }
+
+    #[test]
+    fn test_compact_and_check_order() {
+        let monomer = |start: i32, mon: &str| MonRow {
+            sample: "s".to_string(),
+            start,
+            end: start + MONOMER_LENGTH,
+            mon: mon.to_string(),
+        };
+        // 两侧均为空位的行被去除，类型按两侧标签重新确定
+        let mut rows = vec![
+            row((0, "A"), (0, "A"), "MIS"),
+            row((0, "-"), (0, "-"), "UNX"),
+            row((0, "-"), (0, "B"), "DEL"),
+        ];
+        assert_eq!(compact_alignment(&mut rows), 1);
+        let types: Vec<&str> = rows.iter().map(|r| r.align_type.as_str()).collect();
+        assert_eq!(types, ["MTH", "INS"]);
+
+        let source = [monomer(0, "A")];
+        let target = [monomer(0, "A"), monomer(0, "B")];
+        assert!(check_monomer_order(&rows, &source, &target).is_ok());
+        rows.swap(0, 1);
+        assert!(check_monomer_order(&rows, &source, &target).is_err());
+        assert!(check_monomer_order(&rows[..1], &source, &target[..1]).is_err());
+    }
 }
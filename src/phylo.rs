@@ -0,0 +1,296 @@
+use crate::horscan::{align_with_options, RunOptions};
+use crate::io::{read_bed_file, read_data_lines, write_output, HorScanError, Mode, MonRow};
+use crate::logging::set_progress_enabled;
+use crate::optimize::TypeCount;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+
+/// 由比对类型计数得到归一化距离：非匹配列 (MIS/INS/DEL) 占比对总列数的比例
+pub fn alignment_distance(count: &TypeCount) -> f64 {
+    let total = count.total();
+    if total == 0 {
+        return 0.0;
+    }
+    (count.mis + count.ins + count.del) as f64 / total as f64
+}
+
+/// 读取 BED 列表文件，每行一个 BED 路径
+fn read_bed_list<P: AsRef<Path>>(path: P) -> Result<Vec<String>, HorScanError> {
+    Ok(read_data_lines(path)?
+        .into_iter()
+        .map(|(_, line)| line.trim().to_string())
+        .collect())
+}
+
+/// 以第一个单体的样本名命名序列，重名时追加文件名区分
+fn array_names(paths: &[String], beds: &[Vec<MonRow>]) -> Vec<String> {
+    let samples: Vec<String> = beds
+        .iter()
+        .zip(paths)
+        .map(|(bed, path)| match bed.first() {
+            Some(row) => row.sample.clone(),
+            None => path.clone(),
+        })
+        .collect();
+    samples
+        .iter()
+        .zip(paths)
+        .map(|(sample, path)| {
+            if samples.iter().filter(|s| *s == sample).count() > 1 {
+                let stem = Path::new(path)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.clone());
+                format!("{}|{}", sample, stem)
+            } else {
+                sample.clone()
+            }
+        })
+        .collect()
+}
+
+/// Newick 标签中含有特殊字符时使用单引号包裹
+fn newick_label(name: &str) -> String {
+    if name
+        .chars()
+        .any(|c| "()[]':;,".contains(c) || c.is_whitespace())
+    {
+        format!("'{}'", name.replace('\'', "''"))
+    } else {
+        name.to_string()
+    }
+}
+
+/// 邻接法 (neighbour joining) 构树，返回 Newick 字符串
+pub fn neighbour_joining(names: &[String], distances: &[Vec<f64>]) -> String {
+    let mut nodes: Vec<String> = names.iter().map(|n| newick_label(n)).collect();
+    let mut d: Vec<Vec<f64>> = distances.to_vec();
+
+    if nodes.is_empty() {
+        return ";".to_string();
+    }
+    if nodes.len() == 1 {
+        return format!("{};", nodes[0]);
+    }
+
+    while nodes.len() > 2 {
+        let n = nodes.len();
+        let row_sum: Vec<f64> = d.iter().map(|row| row.iter().sum()).collect();
+
+        // 寻找 Q 矩阵最小的一对节点
+        let mut best = (0, 1);
+        let mut best_q = f64::INFINITY;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let q = (n as f64 - 2.0) * d[i][j] - row_sum[i] - row_sum[j];
+                if q < best_q {
+                    best_q = q;
+                    best = (i, j);
+                }
+            }
+        }
+        let (a, b) = best;
+        let branch_a = 0.5 * d[a][b] + (row_sum[a] - row_sum[b]) / (2.0 * (n as f64 - 2.0));
+        let branch_b = d[a][b] - branch_a;
+        let joined = format!(
+            "({}:{:.6},{}:{:.6})",
+            nodes[a],
+            branch_a.max(0.0),
+            nodes[b],
+            branch_b.max(0.0)
+        );
+
+        // 新节点到其余节点的距离
+        let new_row: Vec<f64> = (0..n)
+            .filter(|&k| k != a && k != b)
+            .map(|k| 0.5 * (d[a][k] + d[b][k] - d[a][b]))
+            .collect();
+
+        // 删除 a、b（先删下标大的），并在末尾追加新节点
+        for idx in [b, a] {
+            nodes.remove(idx);
+            d.remove(idx);
+            for row in d.iter_mut() {
+                row.remove(idx);
+            }
+        }
+        for (row, &dist) in d.iter_mut().zip(&new_row) {
+            row.push(dist);
+        }
+        let mut last = new_row;
+        last.push(0.0);
+        d.push(last);
+        nodes.push(joined);
+    }
+
+    format!(
+        "({}:{:.6},{}:{:.6});",
+        nodes[0],
+        (d[0][1] / 2.0).max(0.0),
+        nodes[1],
+        (d[0][1] / 2.0).max(0.0)
+    )
+}
+
+fn save_distance_matrix(
+    path: &str,
+    names: &[String],
+    distances: &[Vec<f64>],
+) -> Result<(), HorScanError> {
+    write_output(path, |writer| {
+        writeln!(writer, "#name\t{}", names.join("\t"))?;
+        for (name, row) in names.iter().zip(distances) {
+            let values: Vec<String> = row.iter().map(|d| format!("{:.6}", d)).collect();
+            writeln!(writer, "{}\t{}", name, values.join("\t"))?;
+        }
+        Ok(())
+    })
+}
+
+/// 对所有序列两两比对，返回对称的距离矩阵；每对的比对与主命令相同（优化、压缩、相位）
+pub fn distance_matrix(
+    beds: &[Vec<MonRow>],
+    mode: &Mode,
+    options: &RunOptions,
+    threads: usize,
+) -> Result<Vec<Vec<f64>>, HorScanError> {
+    let n = beds.len();
+    let pairs: Vec<(usize, usize)> = (0..n)
+        .flat_map(|i| ((i + 1)..n).map(move |j| (i, j)))
+        .collect();
+
+    let next = Mutex::new(0usize);
+    let distances = Mutex::new(vec![vec![0.0; n]; n]);
+    let failure: Mutex<Option<HorScanError>> = Mutex::new(None);
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let idx = {
+                    let mut next = next.lock().unwrap_or_else(|e| e.into_inner());
+                    *next += 1;
+                    *next - 1
+                };
+                if idx >= pairs.len() {
+                    break;
+                }
+                let (i, j) = pairs[idx];
+                match align_with_options(&beds[i], &beds[j], mode, options) {
+                    Ok(alignment) => {
+                        let distance = alignment_distance(&alignment.count);
+                        let mut distances = distances.lock().unwrap_or_else(|e| e.into_inner());
+                        distances[i][j] = distance;
+                        distances[j][i] = distance;
+                    }
+                    Err(e) => {
+                        let mut failure = failure.lock().unwrap_or_else(|e| e.into_inner());
+                        failure.get_or_insert(HorScanError::AlignmentError(format!(
+                            "pair {} vs {}: {}",
+                            i + 1,
+                            j + 1,
+                            e
+                        )));
+                    }
+                }
+            });
+        }
+    });
+    if let Some(e) = failure.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        return Err(e);
+    }
+    Ok(distances.into_inner().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// all-vs-all 命令：读取 BED 文件（或列表文件），输出距离矩阵和邻接法树
+pub fn run_all_vs_all(
+    mut paths: Vec<String>,
+    list: Option<String>,
+    output_prefix: &str,
+    threads: usize,
+    mode: &Mode,
+    options: &RunOptions,
+) -> Result<(), HorScanError> {
+    if let Some(list) = &list {
+        paths.extend(read_bed_list(list)?);
+    }
+    if paths.len() < 2 {
+        return Err(HorScanError::InvalidValue(
+            "all-vs-all needs at least two BED files".to_string(),
+        ));
+    }
+    let beds = paths
+        .iter()
+        .map(read_bed_file)
+        .collect::<Result<Vec<Vec<MonRow>>, HorScanError>>()?;
+    let names = array_names(&paths, &beds);
+    log::info!(
+        "all-vs-all: {} arrays, {} pairs, {} threads",
+        beds.len(),
+        beds.len() * (beds.len() - 1) / 2,
+        threads.max(1)
+    );
+
+    if threads > 1 {
+        set_progress_enabled(false);
+    }
+    let distances = distance_matrix(&beds, mode, options, threads)?;
+    save_distance_matrix(&format!("{}.dist.tsv", output_prefix), &names, &distances)?;
+    write_output(format!("{}.nwk", output_prefix), |writer| {
+        writeln!(writer, "{}", neighbour_joining(&names, &distances))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::bed;
+
+    #[test]
+    fn test_neighbour_joining_additive() {
+        // 加性树 ((A:2,B:3):1,(C:4,D:1)) 的距离矩阵
+        let names: Vec<String> = ["A", "B", "C", "D"].iter().map(|s| s.to_string()).collect();
+        let d = vec![
+            vec![0.0, 5.0, 7.0, 4.0],
+            vec![5.0, 0.0, 8.0, 5.0],
+            vec![7.0, 8.0, 0.0, 5.0],
+            vec![4.0, 5.0, 5.0, 0.0],
+        ];
+        let tree = neighbour_joining(&names, &d);
+        assert!(tree.ends_with(';'));
+        assert!(tree.contains("(A:2.000000,B:3.000000)"));
+    }
+
+    #[test]
+    fn test_distance_matrix_uses_run_options() -> Result<(), HorScanError> {
+        let beds = vec![
+            bed("a", &"ABCDEFGH".repeat(4)),
+            bed("b", &("EFGH".to_string() + &"ABCDEFGH".repeat(3))),
+            bed("c", &"ABCDEFGH".repeat(3)),
+        ];
+        let mode = Mode::from_params(&[10, 4, 2]);
+        let options = RunOptions {
+            phase: true,
+            ..RunOptions::default()
+        };
+        let distances = distance_matrix(&beds, &mode, &options, 2)?;
+        let expected = align_with_options(&beds[0], &beds[1], &mode, &options)?;
+        assert_eq!(distances[0][1], alignment_distance(&expected.count));
+        assert_eq!(distances[1][0], distances[0][1]);
+        assert!(expected.phase.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_alignment_distance() {
+        let count = TypeCount {
+            mth: 6,
+            ins: 1,
+            del: 1,
+            mis: 2,
+        };
+        assert!((alignment_distance(&count) - 0.4).abs() < 1e-9);
+        assert_eq!(newick_label("CHM13#chrX"), "CHM13#chrX");
+        assert_eq!(newick_label("HG002:mat"), "'HG002:mat'");
+    }
+}
@@ -0,0 +1,95 @@
+use crate::io::{HorScanError, MonRow};
+use std::fmt;
+use std::str::FromStr;
+
+/// 比对区域 `chr:start-end`，chr 与 BED 第一列的样本名对应
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub chrom: String,
+    pub start: i32,
+    pub end: i32,
+}
+
+impl FromStr for Region {
+    type Err = HorScanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 样本名中可能包含 ':'，以最后一个 ':' 分隔坐标
+        let invalid =
+            || HorScanError::InvalidValue(format!("invalid region '{}', expected chr:start-end", s));
+        let (chrom, range) = s.rsplit_once(':').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let start: i32 = start.replace(',', "").parse().map_err(|_| invalid())?;
+        let end: i32 = end.replace(',', "").parse().map_err(|_| invalid())?;
+        if chrom.is_empty() || start < 0 || end <= start {
+            return Err(invalid());
+        }
+        Ok(Region {
+            chrom: chrom.to_string(),
+            start,
+            end,
+        })
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.chrom, self.start, self.end)
+    }
+}
+
+/// 保留与区域重叠的单体（包括跨越边界的单体），坐标保持不变
+pub fn subset_region(rows: Vec<MonRow>, region: &Region) -> Result<Vec<MonRow>, HorScanError> {
+    let total = rows.len();
+    let subset: Vec<MonRow> = rows
+        .into_iter()
+        .filter(|row| {
+            row.sample == region.chrom && row.end > region.start && row.start < region.end
+        })
+        .collect();
+    if subset.is_empty() {
+        return Err(HorScanError::InvalidValue(format!(
+            "no monomer overlaps region {} ({} monomers read)",
+            region, total
+        )));
+    }
+    Ok(subset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mon(start: i32, end: i32) -> MonRow {
+        MonRow {
+            sample: "CHM13#chrX".to_string(),
+            start,
+            end,
+            mon: "A".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_region() -> Result<(), HorScanError> {
+        let region: Region = "CHM13#chrX:1,000-2000".parse()?;
+        assert_eq!(region.chrom, "CHM13#chrX");
+        assert_eq!((region.start, region.end), (1000, 2000));
+        let region: Region = "HG002:mat:5-10".parse()?;
+        assert_eq!(region.chrom, "HG002:mat");
+        assert!("chrX:10-5".parse::<Region>().is_err());
+        assert!("chrX".parse::<Region>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_subset_region_keeps_boundary_monomers() -> Result<(), HorScanError> {
+        let rows = vec![mon(0, 170), mon(171, 341), mon(342, 512), mon(513, 683)];
+        let region: Region = "CHM13#chrX:300-400".parse()?;
+        let subset = subset_region(rows.clone(), &region)?;
+        assert_eq!(subset.len(), 2);
+        assert_eq!((subset[0].start, subset[1].end), (171, 512));
+        let other: Region = "CHM1#chrX:300-400".parse()?;
+        assert!(subset_region(rows, &other).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use crate::horscan::{alignment_score, load_bed, RunOptions};
+use crate::io::{read_blast_records, HorScanError, Mode, MonAlignmentRow, MonRow};
+use crate::labels::apply_label_map;
+use crate::optimize::{row_type, TypeCount};
+use crate::region::subset_region;
+use std::collections::HashMap;
+
+/// 比对文件中的一个问题，line 为比对文件中的行号
+#[derive(Clone, Debug)]
+pub struct AlignmentProblem {
+    pub line: usize,
+    pub detail: String,
+}
+
+fn describe(sample: &str, start: i32, end: i32, mon: &str) -> String {
+    format!("{}:{}-{} {}", sample, start, end, mon)
+}
+
+/// 依次核对比对中一侧的单体，返回问题描述；monomers 为按坐标排序的单体
+struct SideChecker<'a> {
+    name: &'static str,
+    monomers: &'a [MonRow],
+    index: HashMap<(&'a str, i32, i32, &'a str), usize>,
+    next: usize,
+}
+
+impl<'a> SideChecker<'a> {
+    fn new(name: &'static str, monomers: &'a [MonRow]) -> SideChecker<'a> {
+        let index = monomers
+            .iter()
+            .enumerate()
+            .map(|(k, row)| ((row.sample.as_str(), row.start, row.end, row.mon.as_str()), k))
+            .collect();
+        SideChecker {
+            name,
+            monomers,
+            index,
+            next: 0,
+        }
+    }
+
+    fn check(&mut self, sample: &str, start: i32, end: i32, mon: &str) -> Option<String> {
+        let found = self.index.get(&(sample, start, end, mon)).copied();
+        let monomer = describe(sample, start, end, mon);
+        match found {
+            Some(k) if k == self.next => {
+                self.next += 1;
+                None
+            }
+            Some(k) if k > self.next => {
+                let skipped = k - self.next;
+                self.next = k + 1;
+                Some(format!(
+                    "{} {} skips {} {} monomer(s)",
+                    self.name, monomer, skipped, self.name
+                ))
+            }
+            Some(_) => Some(format!(
+                "{} {} is used twice or out of order",
+                self.name, monomer
+            )),
+            None => Some(format!(
+                "{} {} is not in the {} BED",
+                self.name, monomer, self.name
+            )),
+        }
+    }
+
+    fn unused(&self) -> Option<String> {
+        let row = self.monomers.get(self.next)?;
+        Some(format!(
+            "{} {} monomer(s) are not aligned, starting with {}",
+            self.monomers.len() - self.next,
+            self.name,
+            describe(&row.sample, row.start, row.end, &row.mon)
+        ))
+    }
+}
+
+/// 检查比对是否为两组单体的合法全局比对：每个单体恰好出现一次、顺序不变，
+/// 且 align_type 与两侧标签一致（MTH/MIS，source 为空位时 INS，target 为空位时 DEL）；
+/// 旧版本留下的两侧均为空位的 UNX 行被忽略
+///
+/// 返回按两侧标签重新标注类型后的比对行，以及发现的问题
+pub fn check_alignment(
+    records: &[(usize, MonAlignmentRow)],
+    source: &[MonRow],
+    target: &[MonRow],
+) -> (Vec<MonAlignmentRow>, Vec<AlignmentProblem>) {
+    let mut source_side = SideChecker::new("source", source);
+    let mut target_side = SideChecker::new("target", target);
+    let mut rows = Vec::with_capacity(records.len());
+    let mut problems = Vec::new();
+    let mut problem = |line: usize, detail: String| {
+        problems.push(AlignmentProblem { line, detail });
+    };
+
+    for (line, row) in records {
+        let source_gap = row.source_mon == "-";
+        let target_gap = row.target_mon == "-";
+        if source_gap && target_gap {
+            // 优化留下的 UNX 占位行不参与比对
+            if row.align_type != "UNX" {
+                problem(*line, "both sides are gaps".to_string());
+            }
+            continue;
+        }
+        if !source_gap {
+            if let Some(detail) =
+                source_side.check(&row.source, row.source_start, row.source_end, &row.source_mon)
+            {
+                problem(*line, detail);
+            }
+        }
+        if !target_gap {
+            if let Some(detail) =
+                target_side.check(&row.target, row.target_start, row.target_end, &row.target_mon)
+            {
+                problem(*line, detail);
+            }
+        }
+        let expected = row_type(row);
+        if row.align_type != expected {
+            problem(
+                *line,
+                format!("type {} should be {}", row.align_type, expected),
+            );
+        }
+        let mut row = row.clone();
+        row.align_type = expected.to_string();
+        rows.push(row);
+    }
+
+    let last_line = records.last().map(|(line, _)| *line).unwrap_or(0);
+    for side in [&source_side, &target_side] {
+        if let Some(detail) = side.unused() {
+            problem(last_line, detail);
+        }
+    }
+    (rows, problems)
+}
+
+/// score 命令：核对比对文件并输出按 Mode 重新计算的总分和各类型数量
+///
+/// 使用 options 中的校验模式、区域限制和标签映射，应与比对时一致；
+/// 结果以一行 TSV 写到标准输出，问题以 warn 级别报告；存在问题时返回错误
+pub fn run_score(
+    alignment_path: &str,
+    source_path: &str,
+    target_path: &str,
+    mode: &Mode,
+    options: &RunOptions,
+) -> Result<(), HorScanError> {
+    let mut source = load_bed(source_path, options.validation)?;
+    let mut target = load_bed(target_path, options.validation)?;
+    if let Some(path) = &options.label_map {
+        apply_label_map(path, &mut [&mut source, &mut target])?;
+    }
+    if let Some(region) = &options.source_region {
+        source = subset_region(source, region)?;
+    }
+    if let Some(region) = &options.target_region {
+        target = subset_region(target, region)?;
+    }
+    // 与比对时相同的顺序
+    source.sort_by_key(|row| row.start);
+    target.sort_by_key(|row| row.start);
+
+    let records = read_blast_records(alignment_path)?;
+    let (rows, problems) = check_alignment(&records, &source, &target);
+    for problem in &problems {
+        log::warn!("{}:{}: {}", alignment_path, problem.line, problem.detail);
+    }
+
+    let count = TypeCount::from_rows(&rows);
+    println!("#alignment\tvalid\tscore\tMTH\tMIS\tINS\tDEL");
+    println!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        alignment_path,
+        if problems.is_empty() { "yes" } else { "no" },
+        alignment_score(&rows, mode),
+        count.mth,
+        count.mis,
+        count.ins,
+        count.del
+    );
+    if !problems.is_empty() {
+        return Err(HorScanError::InvalidAlignment {
+            path: alignment_path.to_string(),
+            count: problems.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::bed;
+    use crate::horscan::align;
+
+    #[test]
+    fn test_check_alignment() -> Result<(), HorScanError> {
+        let source = bed("s", "ABDE");
+        let target = bed("t", "ABCE");
+        let mode = Mode::from_params(&[10, 4, 2]);
+        let alignment = align(&source, &target, &mode)?;
+        let records: Vec<(usize, MonAlignmentRow)> =
+            alignment.rows.iter().cloned().enumerate().collect();
+        let (rows, problems) = check_alignment(&records, &source, &target);
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(alignment_score(&rows, &mode), alignment.score);
+
+        // 类型写错、漏掉最后一行
+        let mut edited = records.clone();
+        edited[0].1.align_type = "MIS".to_string();
+        edited.pop();
+        let (rows, problems) = check_alignment(&edited, &source, &target);
+        assert_eq!(rows[0].align_type, "MTH");
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].detail.contains("should be MTH"));
+
+        // 同一个单体用了两次
+        let mut doubled = records.clone();
+        doubled.insert(1, records[0].clone());
+        let (_, problems) = check_alignment(&doubled, &source, &target);
+        assert!(problems.iter().any(|p| p.detail.contains("used twice")));
+        Ok(())
+    }
+}
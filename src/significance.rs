@@ -0,0 +1,354 @@
+use crate::hor::{infer_units, segment_labels, DEFAULT_MAX_PERIOD};
+use crate::horscan::{align_with_options, Alignment, RunOptions};
+use crate::io::{write_output, HorScanError, Mode, MonRow};
+use crate::logging::Progress;
+use crate::rng::Rng;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// 置换检验中打乱 target 单体标签的方式，坐标保持不变
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShuffleMode {
+    /// 打乱单个单体，只保留标签组成
+    Monomer,
+    /// 打乱 HOR 拷贝的顺序，保留 HOR 组成；找不到 HOR 单元时退化为 Monomer
+    Hor,
+    /// 保留每种长度为 k 的连续标签组合（k-mer）出现次数的随机重排
+    Kmer(usize),
+}
+
+impl FromStr for ShuffleMode {
+    type Err = HorScanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            HorScanError::InvalidValue(format!(
+                "unknown shuffle mode '{}', expected monomer, hor or kmer:K",
+                s
+            ))
+        };
+        match s {
+            "monomer" => Ok(ShuffleMode::Monomer),
+            "hor" => Ok(ShuffleMode::Hor),
+            "kmer" => Ok(ShuffleMode::Kmer(2)),
+            _ => {
+                let k: usize = s
+                    .strip_prefix("kmer:")
+                    .and_then(|k| k.parse().ok())
+                    .ok_or_else(invalid)?;
+                if k == 0 {
+                    return Err(invalid());
+                }
+                Ok(ShuffleMode::Kmer(k))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ShuffleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShuffleMode::Monomer => write!(f, "monomer"),
+            ShuffleMode::Hor => write!(f, "hor"),
+            ShuffleMode::Kmer(k) => write!(f, "kmer:{}", k),
+        }
+    }
+}
+
+/// 置换检验的设置
+#[derive(Clone, Debug)]
+pub struct PermutationOptions {
+    pub permutations: usize,
+    pub shuffle: ShuffleMode,
+    pub seed: u64,
+}
+
+/// 比对得分与置换得分分布的比较
+#[derive(Clone, Debug)]
+pub struct Significance {
+    pub observed: i32,
+    pub permuted: Vec<i32>,
+    pub mean: f64,
+    pub sd: f64,
+    pub z: f64,
+    /// 经验 p 值 (1 + 不低于观测得分的置换数) / (1 + 置换数)
+    pub p_value: f64,
+}
+
+impl Significance {
+    pub fn new(observed: i32, permuted: Vec<i32>) -> Significance {
+        let n = permuted.len().max(1) as f64;
+        let mean = permuted.iter().map(|&s| s as f64).sum::<f64>() / n;
+        let variance = permuted
+            .iter()
+            .map(|&s| (s as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0).max(1.0);
+        let sd = variance.sqrt();
+        let z = if sd > 0.0 {
+            (observed as f64 - mean) / sd
+        } else if observed as f64 > mean {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+        let extreme = permuted.iter().filter(|&&s| s >= observed).count();
+        Significance {
+            observed,
+            mean,
+            sd,
+            z,
+            p_value: (1 + extreme) as f64 / (1 + permuted.len()) as f64,
+            permuted,
+        }
+    }
+}
+
+/// 保留 k-mer 计数的随机重排（Altschul-Erickson 方法推广到 k）：以 (k-1)-mer 为节点、
+/// 每个 k-mer 为一条边，原序列是从第一个到最后一个节点的欧拉路径，换一条随机的欧拉路径即可。
+/// 各节点的最后一条出边先用 Wilson 算法取一棵指向终点的随机树，其余出边随机排列，
+/// 这样从起点出发总能走完所有的边
+fn klet_shuffle(labels: &[&str], k: usize, rng: &mut Rng) -> Vec<String> {
+    if k < 2 || labels.len() <= k {
+        let mut shuffled: Vec<String> = labels.iter().map(|s| s.to_string()).collect();
+        if k < 2 {
+            rng.shuffle(&mut shuffled);
+        }
+        return shuffled;
+    }
+    let mut node_ids: HashMap<&[&str], usize> = HashMap::new();
+    let path: Vec<usize> = labels
+        .windows(k - 1)
+        .map(|window| {
+            let next = node_ids.len();
+            *node_ids.entry(window).or_insert(next)
+        })
+        .collect();
+    let mut last_label = vec![""; node_ids.len()];
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); node_ids.len()];
+    for (i, pair) in path.windows(2).enumerate() {
+        edges[pair[0]].push(pair[1]);
+        last_label[pair[1]] = labels[i + k - 1];
+    }
+
+    // Wilson 算法：从每个节点沿随机出边走到已在树中的节点，抹去途中的环
+    let (start, end) = (path[0], path[path.len() - 1]);
+    let mut in_tree = vec![false; edges.len()];
+    let mut last_edge = vec![0; edges.len()];
+    in_tree[end] = true;
+    for node in 0..edges.len() {
+        let mut u = node;
+        while !in_tree[u] {
+            last_edge[u] = rng.below(edges[u].len());
+            u = edges[u][last_edge[u]];
+        }
+        let mut u = node;
+        while !in_tree[u] {
+            in_tree[u] = true;
+            u = edges[u][last_edge[u]];
+        }
+    }
+    for (node, out) in edges.iter_mut().enumerate() {
+        if node != end {
+            let last = out.swap_remove(last_edge[node]);
+            rng.shuffle(out);
+            out.push(last);
+        } else {
+            rng.shuffle(out);
+        }
+        out.reverse();
+    }
+
+    let mut shuffled: Vec<String> = labels[..k - 1].iter().map(|s| s.to_string()).collect();
+    let mut node = start;
+    while let Some(next) = edges[node].pop() {
+        shuffled.push(last_label[next].to_string());
+        node = next;
+    }
+    shuffled
+}
+
+/// 按 mode 打乱单体标签，坐标顺序不变
+pub fn shuffle_labels(bed: &mut [MonRow], mode: ShuffleMode, rng: &mut Rng) {
+    let labels: Vec<&str> = bed.iter().map(|row| row.mon.as_str()).collect();
+    let mut blocks: Vec<(usize, usize)> = match mode {
+        ShuffleMode::Monomer => (0..labels.len()).map(|i| (i, i + 1)).collect(),
+        ShuffleMode::Kmer(k) => {
+            let shuffled = klet_shuffle(&labels, k, rng);
+            for (row, label) in bed.iter_mut().zip(shuffled) {
+                row.mon = label;
+            }
+            return;
+        }
+        ShuffleMode::Hor => {
+            let units = infer_units(&labels, DEFAULT_MAX_PERIOD);
+            if units.is_empty() {
+                (0..labels.len()).map(|i| (i, i + 1)).collect()
+            } else {
+                segment_labels(&labels, &units)
+                    .iter()
+                    .map(|copy| (copy.start_index, copy.end_index))
+                    .collect()
+            }
+        }
+    };
+    rng.shuffle(&mut blocks);
+    let shuffled: Vec<String> = blocks
+        .iter()
+        .flat_map(|&(start, end)| labels[start..end].iter().map(|s| s.to_string()))
+        .collect();
+    for (row, label) in bed.iter_mut().zip(shuffled) {
+        row.mon = label;
+    }
+}
+
+/// 置换检验只评价整条全局比对。source 与 target 是同一个输入（同一路径，或单体逐个相同）时
+/// 是自比对，需要对每个局部命中单独检验，目前不支持，直接报错；只是序列名相同的两条阵列照常检验
+pub fn check_permutation_input(
+    source_path: &str,
+    target_path: &str,
+    source: &[MonRow],
+    target: &[MonRow],
+) -> Result<(), HorScanError> {
+    let canonical = |path: &str| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let same_rows = source.len() == target.len()
+        && source.iter().zip(target).all(|(a, b)| {
+            a.sample == b.sample && a.start == b.start && a.end == b.end && a.mon == b.mon
+        });
+    if same_rows || canonical(source_path) == canonical(target_path) {
+        return Err(HorScanError::InvalidValue(
+            "--permutations only tests the whole global alignment, but source and target are the same array; per-hit significance for self-alignment is not supported".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 置换检验：打乱 target 标签（source 不变）后按相同设置重新比对 N 次，与观测比对的得分比较
+pub fn permutation_test(
+    source: &[MonRow],
+    target: &[MonRow],
+    mode: &Mode,
+    options: &RunOptions,
+    observed: &Alignment,
+    permutation: &PermutationOptions,
+) -> Result<Significance, HorScanError> {
+    // 置换比对不写任何中间文件。打乱会破坏 HOR 单元，相位不再重新检测，
+    // 开头不计分的空位数与观测比对相同
+    let free_gaps = observed.phase.as_ref().map(|phase| phase.unit.len() - 1);
+    let options = RunOptions {
+        dump_matrix: None,
+        temp_prefix: None,
+        phase: false,
+        free_gaps: free_gaps.or(options.free_gaps),
+        ..options.clone()
+    };
+    let mut target = target.to_vec();
+    target.sort_by_key(|row| row.start);
+    let mut rng = Rng::new(permutation.seed);
+    let mut permuted = Vec::with_capacity(permutation.permutations);
+    let mut progress = Progress::new("permutations", permutation.permutations);
+    for done in 0..permutation.permutations {
+        progress.update(done);
+        shuffle_labels(&mut target, permutation.shuffle, &mut rng);
+        permuted.push(align_with_options(source, &target, mode, &options)?.score);
+    }
+    progress.finish();
+    Ok(Significance::new(observed.score, permuted))
+}
+
+/// 写出 `<prefix>.significance.tsv`
+pub fn save_significance(
+    path: String,
+    significance: &Significance,
+    permutation: &PermutationOptions,
+) -> Result<(), HorScanError> {
+    write_output(path, |writer| {
+        writeln!(
+            writer,
+            "#score\tpermutations\tshuffle\tseed\tmean\tsd\tz\tp_value"
+        )?;
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{:.2}\t{:.2}\t{:.4}\t{:.6}",
+            significance.observed,
+            significance.permuted.len(),
+            permutation.shuffle,
+            permutation.seed,
+            significance.mean,
+            significance.sd,
+            significance.z,
+            significance.p_value
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::bed;
+
+    #[test]
+    fn test_shuffle_keeps_composition() {
+        let original = bed("chrX", "ABCDABCDABCDABXDABCD");
+        let sorted = |rows: &[MonRow]| {
+            let mut labels: Vec<String> = rows.iter().map(|r| r.mon.clone()).collect();
+            labels.sort();
+            labels
+        };
+        for mode in [ShuffleMode::Monomer, ShuffleMode::Hor, ShuffleMode::Kmer(3)] {
+            let mut rows = original.clone();
+            shuffle_labels(&mut rows, mode, &mut Rng::new(7));
+            assert_eq!(sorted(&rows), sorted(&original));
+            assert_eq!(rows[3].start, original[3].start);
+        }
+        // HOR 打乱后每个 canonical 拷贝仍然完整
+        let mut rows = original.clone();
+        shuffle_labels(&mut rows, ShuffleMode::Hor, &mut Rng::new(7));
+        let labels: String = rows.iter().map(|r| r.mon.as_str()).collect();
+        assert_eq!(labels.matches("ABCD").count(), 4);
+        assert_eq!("kmer:3".parse::<ShuffleMode>().unwrap(), ShuffleMode::Kmer(3));
+    }
+
+    #[test]
+    fn test_kmer_shuffle_keeps_kmers() {
+        let original = bed("chrX", "ABCDABCDABCDABXDABCDAABCDDCBA");
+        let kmers = |rows: &[MonRow], k: usize| {
+            let labels: Vec<&str> = rows.iter().map(|r| r.mon.as_str()).collect();
+            let mut kmers: Vec<String> = labels.windows(k).map(|w| w.concat()).collect();
+            kmers.sort();
+            kmers
+        };
+        for k in [2, 3] {
+            let mut changed = false;
+            let mut rng = Rng::new(k as u64);
+            for _ in 0..20 {
+                let mut rows = original.clone();
+                shuffle_labels(&mut rows, ShuffleMode::Kmer(k), &mut rng);
+                assert_eq!(kmers(&rows, k), kmers(&original, k));
+                changed |= rows.iter().zip(&original).any(|(a, b)| a.mon != b.mon);
+            }
+            assert!(changed);
+        }
+
+        // 两条不同的阵列可以同名（都是 chrX）；同一个输入是自比对，直接报错
+        let other = bed("chrX", "ABCDABCDABCD");
+        assert!(check_permutation_input("s.bed", "t.bed", &original, &other).is_ok());
+        assert!(matches!(
+            check_permutation_input("s.bed", "t.bed", &original, &original),
+            Err(HorScanError::InvalidValue(_))
+        ));
+        assert!(check_permutation_input("s.bed", "s.bed", &original, &other).is_err());
+    }
+
+    #[test]
+    fn test_significance() {
+        let significance = Significance::new(10, vec![1, 2, 3, 10]);
+        assert_eq!(significance.p_value, 0.4);
+        assert_eq!(significance.mean, 4.0);
+        assert!(significance.z > 1.0);
+    }
+}
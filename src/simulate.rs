@@ -0,0 +1,305 @@
+use crate::io::{write_output, HorScanError, MonRow};
+use crate::rng::Rng;
+use std::io::Write;
+
+/// 每种事件的发生率：拷贝级事件按 HOR 拷贝计，替换按单体计
+#[derive(Clone, Debug)]
+pub struct EventRates {
+    /// 不等交换扩增：当前拷贝之后串联插入 1..=max_copies 个完整拷贝
+    pub expansion: f64,
+    /// 不等交换收缩：从当前拷贝起删除 1..=max_copies 个完整拷贝
+    pub contraction: f64,
+    /// 单体替换为单元中的另一个单体
+    pub substitution: f64,
+    /// 拷贝内部插入或删除不足一个单元的连续单体
+    pub partial_indel: f64,
+    /// 拷贝内部一段连续单体倒序
+    pub inversion: f64,
+}
+
+/// simulate 命令的设置
+#[derive(Clone, Debug)]
+pub struct SimulateOptions {
+    pub unit: Vec<String>,
+    pub copies: usize,
+    pub rates: EventRates,
+    /// 扩增/收缩事件最多涉及的拷贝数
+    pub max_copies: usize,
+    /// 单体之间的步长 (bp)，单体长度为步长减 1
+    pub monomer_length: i32,
+    pub source_name: String,
+    pub target_name: String,
+    pub seed: u64,
+}
+
+/// 真值表中的一个事件；区间为单体下标（左闭右开），不涉及的一侧为空区间
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimEvent {
+    /// expansion / contraction / substitution / partial_insertion / partial_deletion / inversion
+    pub kind: &'static str,
+    /// 按 README 中的定义在比对中对应的类型：source 缺少单体为 INS，target 缺少单体为 DEL
+    pub sv_type: &'static str,
+    pub source: (usize, usize),
+    pub target: (usize, usize),
+}
+
+/// 模拟结果：source 为完整拷贝组成的阵列，target 为施加事件后的阵列
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    pub source: Vec<String>,
+    pub target: Vec<String>,
+    pub events: Vec<SimEvent>,
+}
+
+/// 随机取一段 [start, start + len)，len 在 [min_len, max_len] 内
+fn random_run(rng: &mut Rng, size: usize, min_len: usize, max_len: usize) -> (usize, usize) {
+    let len = min_len + rng.below(max_len - min_len + 1);
+    let start = rng.below(size - len + 1);
+    (start, start + len)
+}
+
+/// 逐个 HOR 拷贝生成 target：先决定拷贝级的收缩，再对保留的拷贝至多施加一种拷贝内事件
+/// （倒位、部分缺失/插入或单体替换），最后决定是否在其后扩增
+pub fn simulate(options: &SimulateOptions) -> Simulation {
+    let unit = &options.unit;
+    let period = unit.len();
+    let rates = &options.rates;
+    let mut rng = Rng::new(options.seed);
+    let source: Vec<String> = (0..options.copies).flat_map(|_| unit.iter().cloned()).collect();
+    let mut target: Vec<String> = Vec::with_capacity(source.len());
+    let mut events: Vec<SimEvent> = Vec::new();
+
+    let mut copy = 0;
+    while copy < options.copies {
+        let copy_start = copy * period;
+        if rng.chance(rates.contraction) {
+            let count = (1 + rng.below(options.max_copies)).min(options.copies - copy);
+            events.push(SimEvent {
+                kind: "contraction",
+                sv_type: "DEL",
+                source: (copy_start, copy_start + count * period),
+                target: (target.len(), target.len()),
+            });
+            copy += count;
+            continue;
+        }
+
+        let mut labels: Vec<String> = unit.clone();
+        let base = target.len();
+        if period >= 2 && rng.chance(rates.inversion) {
+            let (start, end) = random_run(&mut rng, period, 2, period);
+            labels[start..end].reverse();
+            events.push(SimEvent {
+                kind: "inversion",
+                sv_type: "INV",
+                source: (copy_start + start, copy_start + end),
+                target: (base + start, base + end),
+            });
+            target.extend(labels);
+        } else if period >= 2 && rng.chance(rates.partial_indel) {
+            let (start, end) = random_run(&mut rng, period, 1, period - 1);
+            if rng.chance(0.5) {
+                events.push(SimEvent {
+                    kind: "partial_deletion",
+                    sv_type: "DEL",
+                    source: (copy_start + start, copy_start + end),
+                    target: (base + start, base + start),
+                });
+                labels.drain(start..end);
+            } else {
+                // 插入的是单元中的一个片段，插在另一个位置之后
+                let at = rng.below(period + 1);
+                let fragment: Vec<String> = unit[start..end].to_vec();
+                events.push(SimEvent {
+                    kind: "partial_insertion",
+                    sv_type: "INS",
+                    source: (copy_start + at, copy_start + at),
+                    target: (base + at, base + at + fragment.len()),
+                });
+                labels.splice(at..at, fragment);
+            }
+            target.extend(labels);
+        } else {
+            for (i, label) in labels.iter_mut().enumerate() {
+                if period >= 2 && rng.chance(rates.substitution) {
+                    let other = (i + 1 + rng.below(period - 1)) % period;
+                    *label = unit[other].clone();
+                    events.push(SimEvent {
+                        kind: "substitution",
+                        sv_type: "MIS",
+                        source: (copy_start + i, copy_start + i + 1),
+                        target: (base + i, base + i + 1),
+                    });
+                }
+            }
+            target.extend(labels);
+        }
+
+        if rng.chance(rates.expansion) {
+            let count = 1 + rng.below(options.max_copies);
+            let source_end = copy_start + period;
+            events.push(SimEvent {
+                kind: "expansion",
+                sv_type: "INS",
+                source: (source_end, source_end),
+                target: (target.len(), target.len() + count * period),
+            });
+            for _ in 0..count {
+                target.extend(unit.iter().cloned());
+            }
+        }
+        copy += 1;
+    }
+    Simulation {
+        source,
+        target,
+        events,
+    }
+}
+
+/// 单体下标对应的 BED 行，步长为 monomer_length
+fn to_bed(sample: &str, labels: &[String], monomer_length: i32) -> Vec<MonRow> {
+    labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| MonRow {
+            sample: sample.to_string(),
+            start: i as i32 * monomer_length,
+            end: i as i32 * monomer_length + monomer_length - 1,
+            mon: label.clone(),
+        })
+        .collect()
+}
+
+/// 单体下标区间的坐标；空区间为该位置单体的起点（末尾之后为阵列终点）
+fn span(bed: &[MonRow], (start, end): (usize, usize), monomer_length: i32) -> (i32, i32) {
+    let position = |idx: usize| {
+        bed.get(idx)
+            .map(|row| row.start)
+            .unwrap_or(idx as i32 * monomer_length)
+    };
+    if start == end {
+        (position(start), position(start))
+    } else {
+        (bed[start].start, bed[end - 1].end)
+    }
+}
+
+fn save_bed(path: String, bed: &[MonRow]) -> Result<(), HorScanError> {
+    write_output(path, |writer| {
+        for row in bed {
+            writeln!(writer, "{}\t{}\t{}\t{}", row.sample, row.start, row.end, row.mon)?;
+        }
+        Ok(())
+    })
+}
+
+/// simulate 命令：写出 `<prefix>.source.bed`、`<prefix>.target.bed` 和真值表 `<prefix>.truth.tsv`
+pub fn run_simulate(options: &SimulateOptions, output_prefix: &str) -> Result<(), HorScanError> {
+    if options.unit.is_empty() || options.copies == 0 {
+        return Err(HorScanError::InvalidValue(
+            "simulate needs a non-empty HOR unit and at least one copy".to_string(),
+        ));
+    }
+    if options.max_copies == 0 || options.monomer_length < 2 {
+        return Err(HorScanError::InvalidValue(
+            "--max-copies must be at least 1 and --monomer-length at least 2".to_string(),
+        ));
+    }
+    let simulation = simulate(options);
+    let source = to_bed(&options.source_name, &simulation.source, options.monomer_length);
+    let target = to_bed(&options.target_name, &simulation.target, options.monomer_length);
+    save_bed(format!("{}.source.bed", output_prefix), &source)?;
+    save_bed(format!("{}.target.bed", output_prefix), &target)?;
+
+    write_output(format!("{}.truth.tsv", output_prefix), |writer| {
+        writeln!(
+            writer,
+            "#id\tkind\tsv_type\tsource\tsource_start\tsource_end\ttarget\ttarget_start\ttarget_end\tsource_monomers\ttarget_monomers"
+        )?;
+        for (id, event) in simulation.events.iter().enumerate() {
+            let (source_start, source_end) = span(&source, event.source, options.monomer_length);
+            let (target_start, target_end) = span(&target, event.target, options.monomer_length);
+            writeln!(
+                writer,
+                "sim{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                id + 1,
+                event.kind,
+                event.sv_type,
+                options.source_name,
+                source_start,
+                source_end,
+                options.target_name,
+                target_start,
+                target_end,
+                event.source.1 - event.source.0,
+                event.target.1 - event.target.0
+            )?;
+        }
+        Ok(())
+    })?;
+    log::info!(
+        "simulated {} source and {} target monomers with {} events (seed {})",
+        source.len(),
+        target.len(),
+        simulation.events.len(),
+        options.seed
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(rates: EventRates, seed: u64) -> SimulateOptions {
+        SimulateOptions {
+            unit: "ABCDEFGH".chars().map(|c| c.to_string()).collect(),
+            copies: 50,
+            rates,
+            max_copies: 3,
+            monomer_length: 171,
+            source_name: "s".to_string(),
+            target_name: "t".to_string(),
+            seed,
+        }
+    }
+
+    fn rates(rate: f64) -> EventRates {
+        EventRates {
+            expansion: rate,
+            contraction: rate,
+            substitution: rate / 4.0,
+            partial_indel: rate,
+            inversion: rate,
+        }
+    }
+
+    #[test]
+    fn test_simulate_is_reproducible_and_consistent() {
+        let a = simulate(&options(rates(0.1), 5));
+        let b = simulate(&options(rates(0.1), 5));
+        assert_eq!((&a.target, &a.events), (&b.target, &b.events));
+        assert!(!a.events.is_empty());
+
+        // 没有事件时 target 与 source 相同
+        let none = simulate(&options(rates(0.0), 5));
+        assert_eq!(none.source, none.target);
+
+        // 按真值表撤销 target 中的增删后长度与 source 一致
+        let mut length = a.target.len() as i64;
+        for event in &a.events {
+            let source_len = (event.source.1 - event.source.0) as i64;
+            let target_len = (event.target.1 - event.target.0) as i64;
+            if event.sv_type == "DEL" || event.sv_type == "INS" {
+                length += source_len - target_len;
+            } else {
+                assert_eq!(source_len, target_len);
+            }
+        }
+        assert_eq!(length, a.source.len() as i64);
+        for event in a.events.iter().filter(|e| e.sv_type == "MIS") {
+            assert_ne!(a.source[event.source.0], a.target[event.target.0]);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::horscan::Alignment;
+use crate::io::{write_output, HorScanError, MonAlignmentRow};
+use crate::logging::{memory_usage_kb, StageRecord};
+use crate::optimize::TypeCount;
+use crate::sv::call_events;
+use std::io::Write;
+
+/// 汇总中统计的事件类型
+pub const EVENT_TYPES: [&str; 3] = ["INS", "DEL", "MIS"];
+
+/// SV 事件按单体数划分的大小等级及其上界（含）
+pub const SIZE_CLASSES: [(&str, usize); 5] = [
+    ("1", 1),
+    ("2-5", 5),
+    ("6-20", 20),
+    ("21-100", 100),
+    (">100", usize::MAX),
+];
+
+/// 事件单体数所属的大小等级
+pub fn size_class(monomers: usize) -> &'static str {
+    SIZE_CLASSES
+        .iter()
+        .find(|(_, max)| monomers <= *max)
+        .map_or(">100", |(name, _)| name)
+}
+
+/// 一次比对的汇总统计
+#[derive(Clone, Debug)]
+pub struct Summary {
+    pub source: String,
+    pub target: String,
+    pub source_monomers: usize,
+    pub target_monomers: usize,
+    pub default_count: TypeCount,
+    pub default_score: i32,
+    pub count: TypeCount,
+    pub score: i32,
+    /// MTH / (MTH + MIS + INS + DEL)
+    pub identity: f64,
+    /// MTH / (MTH + MIS + INS 事件数 + DEL 事件数)：每段连续的空位只计一次
+    pub gap_compressed_identity: f64,
+    /// 按 EVENT_TYPES × SIZE_CLASSES 顺序的 (类型, 大小等级, 事件数)
+    pub events: Vec<(&'static str, &'static str, usize)>,
+    /// 两侧第一个到最后一个 MTH/MIS 单体的区间
+    pub source_span: Option<(i32, i32)>,
+    pub target_span: Option<(i32, i32)>,
+    /// 整个运行的耗时与峰值常驻内存
+    pub seconds: f64,
+    pub peak_rss_kb: Option<u64>,
+    pub stages: Vec<StageRecord>,
+}
+
+/// MTH/MIS 行中一侧的起止坐标
+fn aligned_span(
+    rows: &[MonAlignmentRow],
+    side: fn(&MonAlignmentRow) -> (i32, i32),
+) -> Option<(i32, i32)> {
+    let mut aligned = rows
+        .iter()
+        .filter(|row| row.align_type == "MTH" || row.align_type == "MIS")
+        .map(side);
+    let first = aligned.next()?;
+    let last = aligned.next_back().unwrap_or(first);
+    Some((first.0, last.1))
+}
+
+fn ratio(numerator: i32, denominator: i32) -> f64 {
+    if denominator > 0 {
+        numerator as f64 / denominator as f64
+    } else {
+        0.0
+    }
+}
+
+impl Summary {
+    /// 由比对结果与各阶段记录生成汇总，峰值内存取当前进程的记录
+    pub fn new(alignment: &Alignment, stages: Vec<StageRecord>, seconds: f64) -> Summary {
+        let rows = &alignment.rows;
+        let count = &alignment.count;
+        let called = call_events(rows);
+        let mut events = Summary::empty_events();
+        for event in &called {
+            let class = size_class(event.monomers);
+            if let Some(entry) = events
+                .iter_mut()
+                .find(|(kind, c, _)| *kind == event.kind && *c == class)
+            {
+                entry.2 += 1;
+            }
+        }
+        let gap_events = called.iter().filter(|e| e.kind != "MIS").count() as i32;
+        let sample = |name: fn(&MonAlignmentRow) -> &str, mon: fn(&MonAlignmentRow) -> &str| {
+            rows.iter()
+                .find(|row| mon(row) != "-")
+                .map_or(String::new(), |row| name(row).to_string())
+        };
+        Summary {
+            source: sample(|r| &r.source, |r| &r.source_mon),
+            target: sample(|r| &r.target, |r| &r.target_mon),
+            source_monomers: rows.iter().filter(|r| r.source_mon != "-").count(),
+            target_monomers: rows.iter().filter(|r| r.target_mon != "-").count(),
+            default_count: alignment.default_count.clone(),
+            default_score: alignment.default_score,
+            count: count.clone(),
+            score: alignment.score,
+            identity: ratio(count.mth, count.total()),
+            gap_compressed_identity: ratio(count.mth, count.mth + count.mis + gap_events),
+            events,
+            source_span: aligned_span(rows, |r| (r.source_start, r.source_end)),
+            target_span: aligned_span(rows, |r| (r.target_start, r.target_end)),
+            seconds,
+            peak_rss_kb: memory_usage_kb().1,
+            stages,
+        }
+    }
+
+    /// TSV 的列名，一行汇总对应一行数据，便于多次运行的结果直接拼接
+    pub fn tsv_header() -> String {
+        let mut columns: Vec<String> = [
+            "#source",
+            "target",
+            "source_monomers",
+            "target_monomers",
+            "default_score",
+            "default_MTH",
+            "default_MIS",
+            "default_INS",
+            "default_DEL",
+            "score",
+            "MTH",
+            "MIS",
+            "INS",
+            "DEL",
+            "identity",
+            "gap_compressed_identity",
+            "source_start",
+            "source_end",
+            "target_start",
+            "target_end",
+            "seconds",
+            "peak_rss_kb",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        for (kind, class, _) in Summary::empty_events() {
+            columns.push(format!("{}_{}", kind, class));
+        }
+        columns.join("\t")
+    }
+
+    fn empty_events() -> Vec<(&'static str, &'static str, usize)> {
+        EVENT_TYPES
+            .iter()
+            .flat_map(|&kind| SIZE_CLASSES.iter().map(move |&(class, _)| (kind, class, 0)))
+            .collect()
+    }
+
+    pub fn to_tsv(&self) -> String {
+        let span = |span: Option<(i32, i32)>| {
+            span.map_or([".".to_string(), ".".to_string()], |(start, end)| {
+                [start.to_string(), end.to_string()]
+            })
+        };
+        let count = |c: &TypeCount| [c.mth, c.mis, c.ins, c.del].map(|n| n.to_string());
+        let mut fields = vec![
+            self.source.clone(),
+            self.target.clone(),
+            self.source_monomers.to_string(),
+            self.target_monomers.to_string(),
+            self.default_score.to_string(),
+        ];
+        fields.extend(count(&self.default_count));
+        fields.push(self.score.to_string());
+        fields.extend(count(&self.count));
+        fields.push(format!("{:.6}", self.identity));
+        fields.push(format!("{:.6}", self.gap_compressed_identity));
+        fields.extend(span(self.source_span));
+        fields.extend(span(self.target_span));
+        fields.push(format!("{:.3}", self.seconds));
+        let peak = self
+            .peak_rss_kb
+            .map_or(".".to_string(), |kb| kb.to_string());
+        fields.push(peak);
+        fields.extend(self.events.iter().map(|(_, _, n)| n.to_string()));
+        fields.join("\t")
+    }
+
+    /// 单个 JSON 对象；与 StageRecord::to_json 一样手工拼接
+    pub fn to_json(&self) -> String {
+        let text = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let count = |c: &TypeCount| {
+            format!(
+                "{{\"MTH\":{},\"MIS\":{},\"INS\":{},\"DEL\":{}}}",
+                c.mth, c.mis, c.ins, c.del
+            )
+        };
+        let span = |span: Option<(i32, i32)>| {
+            span.map_or("null".to_string(), |(start, end)| {
+                format!(
+                    "{{\"start\":{},\"end\":{},\"length\":{}}}",
+                    start,
+                    end,
+                    end - start
+                )
+            })
+        };
+        let events: Vec<String> = EVENT_TYPES
+            .iter()
+            .map(|&kind| {
+                let classes: Vec<String> = self
+                    .events
+                    .iter()
+                    .filter(|(k, _, _)| *k == kind)
+                    .map(|(_, class, n)| format!("{}:{}", text(class), n))
+                    .collect();
+                format!("{}:{{{}}}", text(kind), classes.join(","))
+            })
+            .collect();
+        let stages: Vec<String> = self.stages.iter().map(StageRecord::to_json).collect();
+        format!(
+            "{{\"source\":{},\"target\":{},\"source_monomers\":{},\"target_monomers\":{},\
+             \"before_optimization\":{{\"score\":{},\"counts\":{}}},\
+             \"after_optimization\":{{\"score\":{},\"counts\":{}}},\
+             \"identity\":{:.6},\"gap_compressed_identity\":{:.6},\"events\":{{{}}},\
+             \"source_span\":{},\"target_span\":{},\"seconds\":{:.3},\"peak_rss_kb\":{},\"stages\":[{}]}}",
+            text(&self.source),
+            text(&self.target),
+            self.source_monomers,
+            self.target_monomers,
+            self.default_score,
+            count(&self.default_count),
+            self.score,
+            count(&self.count),
+            self.identity,
+            self.gap_compressed_identity,
+            events.join(","),
+            span(self.source_span),
+            span(self.target_span),
+            self.seconds,
+            self.peak_rss_kb
+                .map_or("null".to_string(), |kb| kb.to_string()),
+            stages.join(",")
+        )
+    }
+}
+
+/// 写出 `<prefix>.summary.tsv` 与 `<prefix>.summary.json`
+pub fn save_summary(output_prefix: &str, summary: &Summary) -> Result<(), HorScanError> {
+    write_output(format!("{}.summary.tsv", output_prefix), |writer| {
+        writeln!(writer, "{}", Summary::tsv_header())?;
+        writeln!(writer, "{}", summary.to_tsv())
+    })?;
+    write_output(format!("{}.summary.json", output_prefix), |writer| {
+        writeln!(writer, "{}", summary.to_json())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::bed;
+    use crate::horscan::align;
+    use crate::io::Mode;
+
+    #[test]
+    fn test_summary() -> Result<(), HorScanError> {
+        let source = bed("CHM13#chrX", "ABDEFGHIJK");
+        let target = bed("CHM1#chrX", "ABCDEOGHJK");
+        let alignment = align(&source, &target, &Mode::from_params(&[10, 4, 2]))?;
+        let summary = Summary::new(&alignment, Vec::new(), 1.0);
+        assert_eq!((summary.source_monomers, summary.target_monomers), (10, 10));
+        // 8 MTH, 1 MIS, 1 INS, 1 DEL
+        assert!((summary.identity - 8.0 / 11.0).abs() < 1e-9);
+        assert!((summary.gap_compressed_identity - 8.0 / 11.0).abs() < 1e-9);
+        assert!(summary.events.contains(&("INS", "1", 1)));
+        assert_eq!(summary.source_span, Some((0, 1709)));
+        assert_eq!(
+            summary.to_tsv().split('\t').count(),
+            Summary::tsv_header().split('\t').count()
+        );
+        assert!(summary.to_json().contains("\"events\":{\"INS\":{\"1\":1,"));
+        assert_eq!(size_class(150), ">100");
+        Ok(())
+    }
+}
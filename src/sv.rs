@@ -0,0 +1,121 @@
+use crate::io::MonAlignmentRow;
+use std::fmt;
+
+/// 比对中的一个结构变异事件：连续的同类型非匹配行 (INS/DEL/MIS)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SvEvent {
+    pub kind: String,
+    pub source: String,
+    pub source_start: i32,
+    pub source_end: i32,
+    pub target: String,
+    pub target_start: i32,
+    pub target_end: i32,
+    /// 事件包含的比对行数（单体数）
+    pub monomers: usize,
+}
+
+impl SvEvent {
+    fn from_row(row: &MonAlignmentRow) -> SvEvent {
+        SvEvent {
+            kind: row.align_type.clone(),
+            source: row.source.clone(),
+            source_start: row.source_start,
+            source_end: row.source_end,
+            target: row.target.clone(),
+            target_start: row.target_start,
+            target_end: row.target_end,
+            monomers: 1,
+        }
+    }
+
+    /// 事件在非空位一侧的区间：INS 的 source 为空位，取 target 区间，其余取 source 区间
+    pub fn span(&self) -> (i32, i32) {
+        if self.kind == "INS" {
+            (self.target_start, self.target_end)
+        } else {
+            (self.source_start, self.source_end)
+        }
+    }
+
+    /// 类型相同且非空位一侧的区间重叠
+    pub fn overlaps(&self, other: &SvEvent) -> bool {
+        let (a_start, a_end) = self.span();
+        let (b_start, b_end) = other.span();
+        self.kind == other.kind && a_start < b_end && b_start < a_end
+    }
+}
+
+impl fmt::Display for SvEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}:{}-{} {}:{}-{} ({} monomers)",
+            self.kind,
+            self.source,
+            self.source_start,
+            self.source_end,
+            self.target,
+            self.target_start,
+            self.target_end,
+            self.monomers
+        )
+    }
+}
+
+/// 将相邻的同类型 INS/DEL/MIS 行合并为事件；MTH 行打断事件，UNX 占位行被跳过
+pub fn call_events(rows: &[MonAlignmentRow]) -> Vec<SvEvent> {
+    let mut events: Vec<SvEvent> = Vec::new();
+    let mut open = false;
+    for row in rows {
+        match row.align_type.as_str() {
+            "INS" | "DEL" | "MIS" => {}
+            "UNX" => continue,
+            _ => {
+                open = false;
+                continue;
+            }
+        }
+        match events.last_mut() {
+            Some(event) if open && event.kind == row.align_type => {
+                // 空位一侧的坐标保持为事件起点
+                if row.align_type != "INS" {
+                    event.source_end = row.source_end;
+                }
+                if row.align_type != "DEL" {
+                    event.target_end = row.target_end;
+                }
+                event.monomers += 1;
+            }
+            _ => events.push(SvEvent::from_row(row)),
+        }
+        open = true;
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::row;
+
+    #[test]
+    fn test_call_events_merges_runs() {
+        let rows = vec![
+            row((0, "A"), (0, "A"), "MTH"),
+            row((171, "B"), (171, "-"), "DEL"),
+            row((342, "C"), (171, "-"), "DEL"),
+            row((513, "-"), (171, "-"), "UNX"),
+            row((513, "D"), (171, "E"), "MIS"),
+            row((684, "-"), (342, "F"), "INS"),
+            row((684, "G"), (513, "G"), "MTH"),
+        ];
+        let events = call_events(&rows);
+        assert_eq!(events.len(), 3);
+        assert_eq!((events[0].kind.as_str(), events[0].monomers), ("DEL", 2));
+        assert_eq!(events[0].span(), (171, 512));
+        assert_eq!(events[2].span(), (342, 512));
+        assert!(events[0].overlaps(&SvEvent::from_row(&rows[2])));
+        assert!(!events[0].overlaps(&events[1]));
+    }
+}
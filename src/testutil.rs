@@ -0,0 +1,117 @@
+//! 单元测试共用的临时目录与测试数据构造函数
+
+use crate::eval::TruthEvent;
+use crate::io::{MonAlignmentRow, MonRow};
+use crate::sv::SvEvent;
+
+/// 测试单体的长度（bp），相邻单体起点相隔 `MONOMER_LENGTH + 1`
+pub(crate) const MONOMER_LENGTH: i32 = 170;
+
+/// 测试用的临时目录：以进程号和测试名区分，测试结束（包括失败）时删除
+pub(crate) struct TestDir(std::path::PathBuf);
+
+impl TestDir {
+    pub(crate) fn new(name: &str) -> TestDir {
+        let dir =
+            std::env::temp_dir().join(format!("horscan_test_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+
+    pub(crate) fn path(&self, file: &str) -> std::path::PathBuf {
+        self.0.join(file)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn monomers<'a>(sample: &str, labels: impl Iterator<Item = &'a str>) -> Vec<MonRow> {
+    labels
+        .enumerate()
+        .map(|(i, label)| MonRow {
+            sample: sample.to_string(),
+            start: (MONOMER_LENGTH + 1) * i as i32,
+            end: (MONOMER_LENGTH + 1) * i as i32 + MONOMER_LENGTH,
+            mon: label.to_string(),
+        })
+        .collect()
+}
+
+/// 每个字符一个单体的 BED，如 `bed("chrX", "ABCD")`
+pub(crate) fn bed(sample: &str, labels: &str) -> Vec<MonRow> {
+    let labels: Vec<String> = labels.chars().map(String::from).collect();
+    monomers(sample, labels.iter().map(String::as_str))
+}
+
+/// 以空格分隔多字符标签的 BED，如 `bed_words("chrX", "M1 M2 M3")`
+pub(crate) fn bed_words(sample: &str, labels: &str) -> Vec<MonRow> {
+    monomers(sample, labels.split_whitespace())
+}
+
+/// 一行比对，两侧各为 (起点, 标签)；空位一侧长度为 0
+pub(crate) fn row(source: (i32, &str), target: (i32, &str), kind: &str) -> MonAlignmentRow {
+    row_with_length(MONOMER_LENGTH, source, target, kind)
+}
+
+/// 同 `row`，单体长度为 length
+pub(crate) fn row_with_length(
+    length: i32,
+    source: (i32, &str),
+    target: (i32, &str),
+    kind: &str,
+) -> MonAlignmentRow {
+    let end = |(start, mon): (i32, &str)| if mon == "-" { start } else { start + length };
+    MonAlignmentRow {
+        source: "s".to_string(),
+        source_start: source.0,
+        source_end: end(source),
+        source_mon: source.1.to_string(),
+        target: "t".to_string(),
+        target_start: target.0,
+        target_end: end(target),
+        target_mon: target.1.to_string(),
+        align_type: kind.to_string(),
+    }
+}
+
+/// 真值表中的一个事件，两侧坐标相同的一侧单体数为 0
+pub(crate) fn truth(
+    id: &str,
+    sv_type: &str,
+    source: (i32, i32),
+    target: (i32, i32),
+    size: usize,
+) -> TruthEvent {
+    TruthEvent {
+        id: id.to_string(),
+        kind: "test".to_string(),
+        sv_type: sv_type.to_string(),
+        source: "s".to_string(),
+        source_start: source.0,
+        source_end: source.1,
+        target: "t".to_string(),
+        target_start: target.0,
+        target_end: target.1,
+        source_monomers: if source.0 == source.1 { 0 } else { size },
+        target_monomers: if target.0 == target.1 { 0 } else { size },
+    }
+}
+
+/// 一个调用出的 SV 事件
+pub(crate) fn call(kind: &str, source: (i32, i32), target: (i32, i32), monomers: usize) -> SvEvent {
+    SvEvent {
+        kind: kind.to_string(),
+        source: "s".to_string(),
+        source_start: source.0,
+        source_end: source.1,
+        target: "t".to_string(),
+        target_start: target.0,
+        target_end: target.1,
+        monomers,
+    }
+}
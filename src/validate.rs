@@ -0,0 +1,339 @@
+use crate::io::{HorScanError, MonRow};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// 单体 BED 的校验模式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// 不做任何检查
+    Off,
+    /// 发现任何问题即报错；默认模式，输入不会被静默修改
+    #[default]
+    Strict,
+    /// 报告问题并尽可能修复，需显式开启
+    Lenient,
+}
+
+impl FromStr for ValidationMode {
+    type Err = HorScanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(ValidationMode::Off),
+            "strict" => Ok(ValidationMode::Strict),
+            "lenient" => Ok(ValidationMode::Lenient),
+            _ => Err(HorScanError::InvalidValue(format!(
+                "unknown validation mode '{}', expected off, strict or lenient",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ValidationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValidationMode::Off => "off",
+            ValidationMode::Strict => "strict",
+            ValidationMode::Lenient => "lenient",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 校验发现的问题类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    MixedSample,
+    InvalidLabel,
+    NegativeCoordinate,
+    EndBeforeStart,
+    ZeroLength,
+    Duplicate,
+    Overlap,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IssueKind::MixedSample => "mixed-sample",
+            IssueKind::InvalidLabel => "invalid-label",
+            IssueKind::NegativeCoordinate => "negative-coordinate",
+            IssueKind::EndBeforeStart => "end-before-start",
+            IssueKind::ZeroLength => "zero-length",
+            IssueKind::Duplicate => "duplicate",
+            IssueKind::Overlap => "overlap",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 一条校验问题，line 为该单体在输入文件中的行号
+#[derive(Clone, Debug)]
+pub struct BedIssue {
+    pub line: usize,
+    pub kind: IssueKind,
+    pub detail: String,
+    /// 宽松模式下采取的修复动作，严格模式下为空
+    pub action: Option<String>,
+}
+
+impl BedIssue {
+    fn new(line: usize, kind: IssueKind, detail: String, action: Option<&str>) -> BedIssue {
+        BedIssue {
+            line,
+            kind,
+            detail,
+            action: action.map(|a| a.to_string()),
+        }
+    }
+}
+
+fn describe(row: &MonRow) -> String {
+    format!("{}:{}-{} {}", row.sample, row.start, row.end, row.mon)
+}
+
+/// 检查（宽松模式下同时修复）一组带行号的单体，返回保留的单体和发现的问题
+///
+/// 宽松模式的修复顺序：只保留数量最多的样本，丢弃非法标签，负坐标截断为 0，
+/// 丢弃 end < start 和零长度的单体，按坐标排序后去重，并把与前一个单体重叠的部分裁掉。
+pub fn validate_bed(
+    records: Vec<(usize, MonRow)>,
+    mode: ValidationMode,
+) -> (Vec<MonRow>, Vec<BedIssue>) {
+    if mode == ValidationMode::Off {
+        return (
+            records.into_iter().map(|(_, row)| row).collect(),
+            Vec::new(),
+        );
+    }
+    let lenient = mode == ValidationMode::Lenient;
+    let act = |action: &'static str| if lenient { Some(action) } else { None };
+    let mut issues = Vec::new();
+
+    // 样本名：以出现次数最多的样本为准（次数相同取先出现的）
+    let mut sample_count: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (idx, (_, row)) in records.iter().enumerate() {
+        sample_count
+            .entry(row.sample.as_str())
+            .or_insert((0, idx))
+            .0 += 1;
+    }
+    let main_sample = sample_count
+        .iter()
+        .max_by(|a, b| a.1 .0.cmp(&b.1 .0).then(b.1 .1.cmp(&a.1 .1)))
+        .map(|(sample, _)| sample.to_string())
+        .unwrap_or_default();
+
+    let mut kept: Vec<(usize, MonRow)> = Vec::with_capacity(records.len());
+    for (line, mut row) in records {
+        if row.sample != main_sample {
+            issues.push(BedIssue::new(
+                line,
+                IssueKind::MixedSample,
+                format!("{} differs from main sample {}", row.sample, main_sample),
+                act("dropped"),
+            ));
+            if lenient {
+                continue;
+            }
+        }
+        if row.mon.is_empty() || row.mon == "-" {
+            issues.push(BedIssue::new(
+                line,
+                IssueKind::InvalidLabel,
+                format!("label '{}' is empty or the gap symbol", row.mon),
+                act("dropped"),
+            ));
+            if lenient {
+                continue;
+            }
+        }
+        if row.start < 0 || row.end < 0 {
+            issues.push(BedIssue::new(
+                line,
+                IssueKind::NegativeCoordinate,
+                describe(&row),
+                act("clipped to 0"),
+            ));
+            if lenient {
+                row.start = row.start.max(0);
+                row.end = row.end.max(0);
+            }
+        }
+        if row.end < row.start {
+            issues.push(BedIssue::new(
+                line,
+                IssueKind::EndBeforeStart,
+                describe(&row),
+                act("dropped"),
+            ));
+            if lenient {
+                continue;
+            }
+        } else if row.end == row.start {
+            issues.push(BedIssue::new(
+                line,
+                IssueKind::ZeroLength,
+                describe(&row),
+                act("dropped"),
+            ));
+            if lenient {
+                continue;
+            }
+        }
+        kept.push((line, row));
+    }
+
+    kept.sort_by_key(|(_, row)| (row.start, row.end));
+    let mut rows: Vec<MonRow> = Vec::with_capacity(kept.len());
+    let mut prev: Option<(usize, MonRow)> = None;
+    for (line, mut row) in kept {
+        if let Some((prev_line, prev_row)) = &prev {
+            if prev_row.sample == row.sample
+                && prev_row.start == row.start
+                && prev_row.end == row.end
+                && prev_row.mon == row.mon
+            {
+                issues.push(BedIssue::new(
+                    line,
+                    IssueKind::Duplicate,
+                    format!("{} duplicates line {}", describe(&row), prev_line),
+                    act("dropped"),
+                ));
+                if lenient {
+                    continue;
+                }
+            } else if prev_row.sample == row.sample && row.start < prev_row.end {
+                let contained = row.end <= prev_row.end;
+                issues.push(BedIssue::new(
+                    line,
+                    IssueKind::Overlap,
+                    format!(
+                        "{} overlaps line {} by {} bp",
+                        describe(&row),
+                        prev_line,
+                        row.end.min(prev_row.end) - row.start
+                    ),
+                    act(if contained {
+                        "dropped (contained in previous monomer)"
+                    } else {
+                        "start clipped to previous end"
+                    }),
+                ));
+                if lenient {
+                    if contained {
+                        continue;
+                    }
+                    row.start = prev_row.end;
+                }
+            }
+        }
+        rows.push(row.clone());
+        prev = Some((line, row));
+    }
+    issues.sort_by_key(|issue| issue.line);
+    (rows, issues)
+}
+
+/// 以 warn 级别报告问题列表；严格模式下存在问题时返回错误
+pub fn report_issues(
+    path: &str,
+    issues: &[BedIssue],
+    mode: ValidationMode,
+) -> Result<(), HorScanError> {
+    for issue in issues {
+        match &issue.action {
+            Some(action) => log::warn!(
+                "{}:{}: {}: {} ({})",
+                path, issue.line, issue.kind, issue.detail, action
+            ),
+            None => log::warn!("{}:{}: {}: {}", path, issue.line, issue.kind, issue.detail),
+        }
+    }
+    if mode == ValidationMode::Lenient && !issues.is_empty() {
+        let dropped = issues
+            .iter()
+            .filter(|issue| matches!(&issue.action, Some(action) if action.starts_with("dropped")))
+            .count();
+        log::warn!(
+            "{}: lenient validation changed {} row(s): {} dropped, {} clipped",
+            path,
+            issues.len(),
+            dropped,
+            issues.len() - dropped
+        );
+    }
+    if mode == ValidationMode::Strict && !issues.is_empty() {
+        return Err(HorScanError::InvalidInput {
+            path: path.to_string(),
+            count: issues.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(line: usize, sample: &str, start: i32, end: i32, mon: &str) -> (usize, MonRow) {
+        (
+            line,
+            MonRow {
+                sample: sample.to_string(),
+                start,
+                end,
+                mon: mon.to_string(),
+            },
+        )
+    }
+
+    fn messy_records() -> Vec<(usize, MonRow)> {
+        vec![
+            record(1, "chrX", 0, 170, "A"),
+            record(2, "chrX", 171, 341, "B"),
+            record(3, "chrX", 171, 341, "B"),
+            record(4, "chrX", 300, 512, "C"),
+            record(5, "chrX", 600, 600, "D"),
+            record(6, "chrX", 700, 650, "E"),
+            record(7, "chrY", 800, 970, "F"),
+            record(8, "chrX", 320, 330, "G"),
+        ]
+    }
+
+    #[test]
+    fn test_validate_lenient_repairs() {
+        let (rows, issues) = validate_bed(messy_records(), ValidationMode::Lenient);
+        let kinds: Vec<IssueKind> = issues.iter().map(|i| i.kind).collect();
+        assert!(kinds.contains(&IssueKind::MixedSample));
+        assert!(kinds.contains(&IssueKind::ZeroLength));
+        assert!(kinds.contains(&IssueKind::EndBeforeStart));
+        assert!(kinds.contains(&IssueKind::Duplicate));
+        assert_eq!(
+            issues
+                .iter()
+                .filter(|i| i.kind == IssueKind::Overlap)
+                .count(),
+            2
+        );
+        let spans: Vec<(i32, i32, &str)> = rows
+            .iter()
+            .map(|r| (r.start, r.end, r.mon.as_str()))
+            .collect();
+        assert_eq!(spans, [(0, 170, "A"), (171, 341, "B"), (341, 512, "C")]);
+    }
+
+    #[test]
+    fn test_validate_strict_reports_without_repair() {
+        let (rows, issues) = validate_bed(messy_records(), ValidationMode::Strict);
+        assert_eq!(rows.len(), 8);
+        assert!(issues.iter().all(|i| i.action.is_none()));
+        assert!(report_issues("x.bed", &issues, ValidationMode::Strict).is_err());
+        let (_, clean) = validate_bed(messy_records()[..2].to_vec(), ValidationMode::Strict);
+        assert!(clean.is_empty());
+        // 默认不修复输入
+        assert_eq!(ValidationMode::default(), ValidationMode::Strict);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::io::{read_blast_file, write_output, HorScanError, MonAlignmentRow};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+/// 窗口大小或步长：单体数，或带 bp/kb 后缀的碱基数
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowSize {
+    Monomers(usize),
+    Bases(i32),
+}
+
+impl FromStr for WindowSize {
+    type Err = HorScanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            HorScanError::InvalidValue(format!(
+                "invalid window size '{}', expected a number of monomers (e.g. 20) or bases (e.g. 5000bp, 10kb)",
+                s
+            ))
+        };
+        let lower = s.to_ascii_lowercase();
+        let size = if let Some(kb) = lower.strip_suffix("kb") {
+            kb.parse::<i32>()
+                .ok()
+                .and_then(|kb| kb.checked_mul(1000))
+                .map(WindowSize::Bases)
+        } else if let Some(bp) = lower.strip_suffix("bp") {
+            bp.parse().ok().map(WindowSize::Bases)
+        } else {
+            lower.parse().ok().map(WindowSize::Monomers)
+        };
+        match size {
+            Some(WindowSize::Monomers(n)) if n > 0 => Ok(WindowSize::Monomers(n)),
+            Some(WindowSize::Bases(n)) if n > 0 => Ok(WindowSize::Bases(n)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowSize::Monomers(n) => write!(f, "{} monomers", n),
+            WindowSize::Bases(n) => write!(f, "{}bp", n),
+        }
+    }
+}
+
+/// 一个窗口的统计
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowStat {
+    pub start: i32,
+    pub end: i32,
+    pub mth: usize,
+    pub mis: usize,
+    /// INS 与 DEL 行数
+    pub gaps: usize,
+}
+
+impl WindowStat {
+    /// MTH / (MTH + MIS)，窗口内没有比对上的单体时为 None
+    pub fn identity(&self) -> Option<f64> {
+        let aligned = self.mth + self.mis;
+        (aligned > 0).then(|| self.mth as f64 / aligned as f64)
+    }
+
+    /// 空位行占窗口内比对行的比例
+    pub fn indel_density(&self) -> f64 {
+        self.gaps as f64 / (self.mth + self.mis + self.gaps) as f64
+    }
+}
+
+/// 比对行在一侧的位置：所属单体的序号、坐标与比对类型
+struct Entry<'a> {
+    index: usize,
+    start: i32,
+    end: i32,
+    kind: &'a str,
+}
+
+type SideFields = fn(&MonAlignmentRow) -> (&str, i32, i32, &str);
+
+fn source_fields(row: &MonAlignmentRow) -> (&str, i32, i32, &str) {
+    (
+        &row.source,
+        row.source_start,
+        row.source_end,
+        &row.source_mon,
+    )
+}
+
+fn target_fields(row: &MonAlignmentRow) -> (&str, i32, i32, &str) {
+    (
+        &row.target,
+        row.target_start,
+        row.target_end,
+        &row.target_mon,
+    )
+}
+
+/// 沿一侧坐标滑动窗口。该侧为空位的行位于前一个单体处（与比对文件中空位的坐标一致），
+/// 计入包含该单体的窗口
+pub fn sliding_windows(
+    rows: &[MonAlignmentRow],
+    side: SideFields,
+    window: WindowSize,
+    step: WindowSize,
+) -> Result<Vec<WindowStat>, HorScanError> {
+    let mut entries = Vec::with_capacity(rows.len());
+    let mut monomers = 0;
+    for row in rows {
+        if !matches!(row.align_type.as_str(), "MTH" | "MIS" | "INS" | "DEL") {
+            continue;
+        }
+        let (_, start, end, mon) = side(row);
+        let index = if mon == "-" {
+            monomers.max(1) - 1
+        } else {
+            monomers += 1;
+            monomers - 1
+        };
+        entries.push(Entry {
+            index,
+            start,
+            end,
+            kind: &row.align_type,
+        });
+    }
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // 每个窗口对应 entries 中的 [a, b)：按单体序号或坐标划分，两者都随比对行单调不减
+    let key = |entry: &Entry| -> i64 {
+        match window {
+            WindowSize::Monomers(_) => entry.index as i64,
+            WindowSize::Bases(_) => entry.start as i64,
+        }
+    };
+    let (size, step, first) = match (window, step) {
+        (WindowSize::Monomers(size), WindowSize::Monomers(step)) => (size as i64, step as i64, 0),
+        (WindowSize::Bases(size), WindowSize::Bases(step)) => {
+            let first = entries[0].start as i64;
+            (
+                size as i64,
+                step as i64,
+                first - first.rem_euclid(step as i64),
+            )
+        }
+        _ => {
+            return Err(HorScanError::InvalidValue(format!(
+                "window {} and step {} must both be in monomers or both in bases",
+                window, step
+            )))
+        }
+    };
+    let last = key(&entries[entries.len() - 1]);
+
+    let mut windows = Vec::new();
+    let mut low = first;
+    loop {
+        let high = low + size;
+        let a = entries.partition_point(|e| key(e) < low);
+        let b = entries.partition_point(|e| key(e) < high);
+        if a < b {
+            let range = &entries[a..b];
+            let count = |kinds: &[&str]| range.iter().filter(|e| kinds.contains(&e.kind)).count();
+            let (mut start, mut end) = (
+                range[0].start,
+                range.iter().map(|e| e.end).max().unwrap_or(0),
+            );
+            if let WindowSize::Bases(_) = window {
+                start = start.max(low as i32);
+                end = end.min(high as i32);
+            }
+            windows.push(WindowStat {
+                start,
+                end,
+                mth: count(&["MTH"]),
+                mis: count(&["MIS"]),
+                gaps: count(&["INS", "DEL"]),
+            });
+        }
+        if high > last {
+            break;
+        }
+        low += step;
+    }
+    Ok(windows)
+}
+
+fn save_bedgraph(
+    path: String,
+    name: &str,
+    chrom: &str,
+    windows: &[WindowStat],
+    value: fn(&WindowStat) -> Option<f64>,
+) -> Result<(), HorScanError> {
+    write_output(path, |writer| {
+        writeln!(writer, "track type=bedGraph name=\"{}\"", name)?;
+        for window in windows {
+            if let Some(v) = value(window) {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{:.4}",
+                    chrom, window.start, window.end, v
+                )?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// window 命令：对两侧各写出 `<prefix>.<side>.identity.bedGraph` 与 `<prefix>.<side>.indel.bedGraph`
+pub fn run_window(
+    alignment_path: &str,
+    window: WindowSize,
+    step: Option<WindowSize>,
+    output_prefix: &str,
+) -> Result<(), HorScanError> {
+    let rows = read_blast_file(alignment_path)?;
+    let step = step.unwrap_or(window);
+    let sides: [(&str, SideFields); 2] = [("source", source_fields), ("target", target_fields)];
+    for (name, side) in sides {
+        let windows = sliding_windows(&rows, side, window, step)?;
+        let chrom = rows
+            .iter()
+            .map(side)
+            .find(|(_, _, _, mon)| *mon != "-")
+            .map_or(String::new(), |(chrom, _, _, _)| chrom.to_string());
+        save_bedgraph(
+            format!("{}.{}.identity.bedGraph", output_prefix, name),
+            &format!("{} identity", chrom),
+            &chrom,
+            &windows,
+            WindowStat::identity,
+        )?;
+        save_bedgraph(
+            format!("{}.{}.indel.bedGraph", output_prefix, name),
+            &format!("{} indel density", chrom),
+            &chrom,
+            &windows,
+            |w| Some(w.indel_density()),
+        )?;
+        log::info!(
+            "{} {}: {} windows of {}",
+            name,
+            chrom,
+            windows.len(),
+            window
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::row;
+
+    #[test]
+    fn test_sliding_windows() -> Result<(), HorScanError> {
+        let rows = vec![
+            row((0, "A"), (0, "A"), "MTH"),
+            row((171, "B"), (171, "C"), "MIS"),
+            row((171, "-"), (342, "X"), "INS"),
+            row((342, "C"), (513, "C"), "MTH"),
+            row((513, "D"), (513, "-"), "DEL"),
+        ];
+        let two = WindowSize::Monomers(2);
+        let windows = sliding_windows(&rows, source_fields, two, two)?;
+        assert_eq!(windows.len(), 2);
+        // 第一个窗口 A、B 以及 B 之后的 INS
+        assert_eq!((windows[0].start, windows[0].end), (0, 341));
+        assert_eq!(windows[0].identity(), Some(0.5));
+        assert!((windows[0].indel_density() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!((windows[1].mth, windows[1].gaps), (1, 1));
+
+        // target 一侧按碱基划分，窗口与坐标对齐
+        let windows = sliding_windows(
+            &rows,
+            target_fields,
+            "342bp".parse()?,
+            WindowSize::Bases(342),
+        )?;
+        assert_eq!(windows.len(), 2);
+        assert_eq!(
+            (windows[1].start, windows[1].mth, windows[1].gaps),
+            (342, 1, 2)
+        );
+        assert_eq!("10kb".parse::<WindowSize>()?, WindowSize::Bases(10000));
+        assert!("0".parse::<WindowSize>().is_err());
+        assert!(sliding_windows(&rows, source_fields, two, WindowSize::Bases(5)).is_err());
+        Ok(())
+    }
+}